@@ -5,16 +5,15 @@ fn main() {}
 use linux_embedded_hal::{
     spidev::{SpiModeFlags, SpidevOptions},
     sysfs_gpio::Direction,
-    Delay, Pin, Spidev,
+    Delay, SpidevDevice, SysfsPin,
 };
 
 #[cfg(target_os = "linux")]
 use embedded_graphics::{
-    fonts::{Font6x8, Text},
+    mono_font::{ascii::FONT_6X9, MonoTextStyleBuilder},
     prelude::*,
-    primitives::{Circle, Line, Rectangle},
-    style::PrimitiveStyle,
-    text_style,
+    primitives::{Circle, Line, PrimitiveStyle, Rectangle},
+    text::Text,
 };
 
 #[cfg(target_os = "linux")]
@@ -26,7 +25,7 @@ use ssd1681::{color::*, prelude::*, WIDTH};
 // see https://github.com/rust-embedded/rust-sysfs-gpio/issues/5 and follow-up issues
 fn main() -> Result<(), std::io::Error> {
     // Configure SPI
-    let mut spi = Spidev::open("/dev/spidev0.0").expect("SPI device");
+    let mut spi = SpidevDevice::open("/dev/spidev0.0").expect("SPI device");
     let options = SpidevOptions::new()
         .bits_per_word(8)
         .max_speed_hz(1_000_000)
@@ -35,14 +34,8 @@ fn main() -> Result<(), std::io::Error> {
     spi.configure(&options).expect("SPI configuration");
 
     // https://pinout.xyz/
-    // Configure Digital I/O Pins
-    let cs = Pin::new(2); // GPIO/BCM 8, pin 24
-    cs.export().expect("cs export");
-    while !cs.is_exported() {}
-    cs.set_direction(Direction::Out).expect("CS Direction");
-    cs.set_value(1).expect("CS Value set to 1");
-
-    let reset = Pin::new(7); // GPIO/BCM 7, pin 26
+    // Configure Digital I/O Pins, chip select is driven by spidev
+    let reset = SysfsPin::new(7); // GPIO/BCM 7, pin 26
     reset.export().expect("reset export");
     while !reset.is_exported() {}
     reset
@@ -50,12 +43,12 @@ fn main() -> Result<(), std::io::Error> {
         .expect("reset Direction");
     reset.set_value(1).expect("reset Value set to 1");
 
-    let busy = Pin::new(1); // GPIO/BCM 1, pin 28
+    let busy = SysfsPin::new(1); // GPIO/BCM 1, pin 28
     busy.export().expect("busy export");
     while !busy.is_exported() {}
     busy.set_direction(Direction::In).expect("busy Direction");
 
-    let dc = Pin::new(12); // GPIO/BCM 12, pin 32
+    let dc = SysfsPin::new(12); // GPIO/BCM 12, pin 32
     dc.export().expect("dc export");
     while !dc.is_exported() {}
     dc.set_direction(Direction::Out).expect("dc Direction");
@@ -66,11 +59,11 @@ fn main() -> Result<(), std::io::Error> {
     let mut delay = Delay {};
 
     // Initialise display controller
-    let mut ssd1681 = Ssd1681::new(&mut spi, cs, busy, dc, reset, &mut delay).unwrap();
+    let mut ssd1681 = Ssd1681::new(spi, busy, dc, reset, &mut delay).unwrap();
 
     // Clear frames on the display driver
-    ssd1681.clear_red_frame(&mut spi)?;
-    ssd1681.clear_bw_frame(&mut spi)?;
+    ssd1681.clear_red_frame().expect("clear red");
+    ssd1681.clear_bw_frame().expect("clear bw");
 
     // Create buffer for black and white
     let mut display_bw = Display1in54::bw();
@@ -78,13 +71,15 @@ fn main() -> Result<(), std::io::Error> {
     draw_rotation_and_rulers(&mut display_bw);
 
     display_bw.set_rotation(DisplayRotation::Rotate0);
-    Rectangle::new(Point::new(60, 60), Point::new(100, 100))
+    Rectangle::new(Point::new(60, 60), Size::new(40, 40))
         .into_styled(PrimitiveStyle::with_fill(Black))
         .draw(&mut display_bw)
         .unwrap();
 
     println!("Send bw frame to display");
-    ssd1681.update_bw_frame(&mut spi, display_bw.buffer())?;
+    ssd1681
+        .update_bw_frame(display_bw.buffer())
+        .expect("update bw");
 
     // Draw red color
     let mut display_red = Display1in54::red();
@@ -95,10 +90,12 @@ fn main() -> Result<(), std::io::Error> {
         .unwrap();
 
     // println!("Send red frame to display");
-    ssd1681.update_red_frame(&mut spi, display_red.buffer())?;
+    ssd1681
+        .update_red_frame(display_red.buffer())
+        .expect("update red");
 
     println!("Update display");
-    ssd1681.display_frame(&mut spi)?;
+    ssd1681.display_frame().expect("display frame");
 
     println!("Done");
     Ok(())
@@ -142,11 +139,10 @@ fn draw_ruler(display: &mut Display1in54) {
 
 #[cfg(target_os = "linux")]
 fn draw_text(display: &mut Display1in54, text: &str, x: i32, y: i32) {
-    let _ = Text::new(text, Point::new(x, y))
-        .into_styled(text_style!(
-            font = Font6x8,
-            text_color = Black,
-            background_color = White
-        ))
-        .draw(display);
+    let style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X9)
+        .text_color(Black)
+        .background_color(White)
+        .build();
+    let _ = Text::new(text, Point::new(x, y), style).draw(display);
 }
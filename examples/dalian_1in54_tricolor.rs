@@ -19,8 +19,7 @@
 //#![no_std]
 //#![no_main]
 
-
-#[cfg(not(target_os = "linux"))]
+#[cfg(not(target_arch = "arm"))]
 fn main() {}
 
 #[cfg(target_arch = "arm")]
@@ -42,25 +41,17 @@ use embedded_graphics::{
     Drawable,
 };
 #[cfg(target_arch = "arm")]
-use embedded_hal::{
-    delay::DelayNs,
-    digital::StatefulOutputPin,
-};
+use embedded_hal::{delay::DelayNs, digital::StatefulOutputPin};
 #[cfg(target_arch = "arm")]
 use embedded_hal_bus::spi::ExclusiveDevice;
 #[cfg(target_arch = "arm")]
 use rp_pico::{
     entry,
-    pac,
     hal::{
-        clocks::init_clocks_and_plls,
-        fugit::RateExtU32,
-        gpio::FunctionSpi,
-        spi,
-        Clock,
-        Sio,
+        clocks::init_clocks_and_plls, fugit::RateExtU32, gpio::FunctionSpi, spi, Clock, Sio,
         Watchdog,
     },
+    pac,
 };
 #[cfg(target_arch = "arm")]
 use ssd1681::{
@@ -113,7 +104,6 @@ fn main() -> ! {
     let miso = pins.gpio16.into_function::<FunctionSpi>(); // SDA RX
     let cs_spi = pins.gpio21.into_push_pull_output();
 
-    let cs = pins.gpio17.into_push_pull_output();
     let dc = pins.gpio12.into_push_pull_output();
     let rst = pins.gpio11.into_push_pull_output();
     let busy = pins.gpio13.into_pull_down_input();
@@ -126,14 +116,14 @@ fn main() -> ! {
         embedded_hal::spi::MODE_0,
     );
 
-    let mut spi_device = ExclusiveDevice::new_no_delay(spi, cs_spi).unwrap();
+    let spi_device = ExclusiveDevice::new_no_delay(spi, cs_spi).unwrap();
 
     // Initialize display controller
-    let mut ssd1681 = Ssd1681::new(&mut spi_device, cs, busy, dc, rst, &mut delay).unwrap();
+    let mut ssd1681 = Ssd1681::new(spi_device, busy, dc, rst, &mut delay).unwrap();
 
     // Clear frames on the display driver
-    ssd1681.clear_red_frame(&mut spi_device);
-    ssd1681.clear_bw_frame(&mut spi_device);
+    ssd1681.clear_red_frame();
+    ssd1681.clear_bw_frame();
 
     // Create buffer for black and white
     let mut display_bw = Display1in54::bw();
@@ -147,7 +137,7 @@ fn main() -> ! {
         .unwrap();
 
     println!("Send bw frame to display");
-    ssd1681.update_bw_frame(&mut spi_device, display_bw.buffer());
+    ssd1681.update_bw_frame(display_bw.buffer());
 
     // Draw red color
     let mut display_red = Display1in54::red();
@@ -158,10 +148,10 @@ fn main() -> ! {
         .unwrap();
 
     println!("Send red frame to display");
-    ssd1681.update_red_frame(&mut spi_device, display_red.buffer());
+    ssd1681.update_red_frame(display_red.buffer());
 
     println!("Update display");
-    ssd1681.display_frame(&mut spi_device);
+    ssd1681.display_frame();
 
     println!("Done");
 
@@ -0,0 +1,237 @@
+//! Drawing a frame one band of rows at a time
+//!
+//! [`BandRenderer`] needs a buffer for a few rows of both planes instead of two whole frame
+//! buffers. The scene is drawn once per band, every band only keeps the pixels inside of it
+//! and is written into the matching RAM window right away. With 16 rows of the 1.54" panel
+//! that is 800 bytes for a full tri-color frame.
+//!
+//! Since the scene is drawn again for every band it has to draw the same thing each time.
+//! Software rotation is not supported, use
+//! [hardware rotation](crate::graphics::DisplayRotation::data_entry_mode) instead.
+
+use core::convert::Infallible;
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use crate::color::TriColor;
+use crate::controller::{Controller, PanelSize};
+use crate::driver::{FrameArea, Ssd1681};
+use crate::interface::{DisplayError, WriteOnlyDataCommand};
+use crate::pin::{BusyPin, ResetPin};
+use crate::ram::RamPlane;
+
+/// Draws a frame band by band, see the [module documentation](self)
+pub struct BandRenderer<'a> {
+    buffer: &'a mut [u8],
+}
+
+impl<'a> BandRenderer<'a> {
+    /// Use `buffer` for the bands
+    ///
+    /// Each band takes as many rows as fit into it, a row of both planes takes a quarter of
+    /// the panel width in bytes.
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        BandRenderer { buffer }
+    }
+
+    /// Rows per band on a panel of `size`
+    pub fn band_height(&self, size: PanelSize) -> u16 {
+        let rows = self.buffer.len() / (2 * size.row_len());
+        rows.min(usize::from(size.height)) as u16
+    }
+
+    /// Draw `scene` into every band and write the bands into the RAM of `ssd1681`
+    ///
+    /// Show the frame with [`display_frame`](Ssd1681::display_frame) afterwards.
+    ///
+    /// # Panics
+    ///
+    /// If the buffer does not hold a single row of both planes.
+    pub fn render<DI, BUSY, RST, C, F, const SHADOW_ROWS: usize>(
+        &mut self,
+        ssd1681: &mut Ssd1681<DI, BUSY, RST, C, SHADOW_ROWS>,
+        mut scene: F,
+    ) -> Result<(), DisplayError>
+    where
+        DI: WriteOnlyDataCommand,
+        BUSY: BusyPin,
+        RST: ResetPin,
+        C: Controller,
+        F: FnMut(&mut Band<'_>),
+    {
+        let size = ssd1681.size();
+        let rows = self.band_height(size);
+        assert!(rows > 0, "buffer too small for a single row");
+
+        let row_len = size.row_len();
+        let mut top = 0;
+        while top < size.height {
+            let height = rows.min(size.height - top);
+            let len = row_len * usize::from(height);
+            let (bw, red) = self.buffer.split_at_mut(len);
+            let mut band = Band {
+                bw,
+                red: &mut red[..len],
+                size,
+                top,
+                height,
+            };
+            band.clear();
+            scene(&mut band);
+
+            let area = FrameArea {
+                x: 0,
+                y: u32::from(top),
+                width: row_len as u32 * 8,
+                height: u32::from(height),
+            };
+            ssd1681.write_area(RamPlane::BlackWhite, area, band.bw)?;
+            ssd1681.write_area(RamPlane::Red, area, band.red)?;
+            top += height;
+        }
+        Ok(())
+    }
+}
+
+/// Rows of the frame currently drawn by a [`BandRenderer`]
+///
+/// Covers the whole panel, pixels outside of the band are dropped.
+pub struct Band<'a> {
+    bw: &'a mut [u8],
+    red: &'a mut [u8],
+    size: PanelSize,
+    top: u16,
+    height: u16,
+}
+
+impl Band<'_> {
+    /// The rows of the frame this band holds
+    ///
+    /// Scenes that are expensive to draw can skip what is outside of it.
+    pub fn area(&self) -> Rectangle {
+        Rectangle::new(
+            Point::new(0, i32::from(self.top)),
+            Size::new(u32::from(self.size.width), u32::from(self.height)),
+        )
+    }
+
+    fn clear(&mut self) {
+        let (bw, red) = TriColor::White.plane_bytes();
+        self.bw.fill(bw);
+        self.red.fill(red);
+    }
+}
+
+fn set_bit(byte: &mut u8, bit: u8, set: bool) {
+    if set {
+        *byte |= bit;
+    } else {
+        *byte &= !bit;
+    }
+}
+
+impl Dimensions for Band<'_> {
+    fn bounding_box(&self) -> Rectangle {
+        Rectangle::new(
+            Point::zero(),
+            Size::new(u32::from(self.size.width), u32::from(self.size.height)),
+        )
+    }
+}
+
+impl DrawTarget for Band<'_> {
+    type Color = TriColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let area = self.area();
+        for Pixel(point, color) in pixels.into_iter() {
+            if !area.contains(point) {
+                continue;
+            }
+            let (x, y) = (point.x as usize, (point.y as usize) - usize::from(self.top));
+            let index = y * self.size.row_len() + x / 8;
+            let bit = 0x80 >> (x % 8);
+            let (bw, red) = color.plane_bits();
+            if let Some(set) = bw {
+                set_bit(&mut self.bw[index], bit, set);
+            }
+            set_bit(&mut self.red[index], bit, red);
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        // only the part inside of the band is worth iterating over
+        let area = area.intersection(&self.area());
+        self.draw_iter(area.points().map(|point| Pixel(point, color)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::BandRenderer;
+    use crate::cmd;
+    use crate::color::TriColor;
+    use crate::mock::{NoDelay, Sim};
+    use embedded_graphics::{
+        prelude::*,
+        primitives::{Circle, PrimitiveStyle, Rectangle},
+    };
+
+    fn scene<D: DrawTarget<Color = TriColor>>(target: &mut D) {
+        let _ = Rectangle::new(Point::new(3, 10), Size::new(150, 30))
+            .into_styled(PrimitiveStyle::with_fill(TriColor::Black))
+            .draw(target);
+        let _ = Circle::new(Point::new(60, 20), 100)
+            .into_styled(PrimitiveStyle::with_stroke(TriColor::Red, 3))
+            .draw(target);
+    }
+
+    // Every plane as it ends up in the controller RAM
+    fn planes(sim: &Sim) -> (Vec<u8>, Vec<u8>) {
+        let (mut bw, mut red) = (Vec::new(), Vec::new());
+        for (command, data) in sim.commands() {
+            match command {
+                cmd::WRITE_BW_DATA => bw.extend(data),
+                cmd::WRITE_RED_DATA => red.extend(data),
+                _ => {}
+            }
+        }
+        (bw, red)
+    }
+
+    #[test]
+    fn bands_match_full_frame() {
+        let sim = Sim::new();
+        let mut screen = sim.screen();
+        scene(&mut screen);
+        screen.flush(&mut NoDelay).unwrap();
+        let expected = planes(&sim);
+
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        let mut buffer = [0; 800];
+        let mut renderer = BandRenderer::new(&mut buffer);
+        assert_eq!(renderer.band_height(ssd1681.size()), 16);
+        sim.clear();
+        renderer.render(&mut ssd1681, |band| scene(band)).unwrap();
+
+        let bands = sim
+            .commands()
+            .iter()
+            .filter(|(command, _)| *command == cmd::WRITE_BW_DATA)
+            .count();
+        assert_eq!(bands, 13);
+        assert_eq!(sim.data_for(cmd::SET_RAMYPOS).unwrap(), [192, 0, 199, 0]);
+        assert_eq!(planes(&sim), expected);
+    }
+}
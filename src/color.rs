@@ -3,13 +3,13 @@
 #[cfg(feature = "graphics")]
 use embedded_graphics::pixelcolor::BinaryColor;
 
+use embedded_graphics::pixelcolor::PixelColor;
 #[cfg(feature = "graphics")]
 pub use BinaryColor::Off as White;
 #[cfg(feature = "graphics")]
 pub use BinaryColor::On as Black;
 #[cfg(feature = "graphics")]
 pub use BinaryColor::On as Red;
-use embedded_graphics::pixelcolor::PixelColor;
 
 /// Black/White colors
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -24,6 +24,44 @@ impl PixelColor for Color {
     type Raw = ();
 }
 
+/// Colors of a tri-color panel
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TriColor {
+    /// White color
+    White,
+    /// Black color
+    Black,
+    /// Red color, shown instead of whatever the black and white plane holds
+    Red,
+}
+
+impl PixelColor for TriColor {
+    type Raw = ();
+}
+
+impl TriColor {
+    /// Bits of the color in the black and white and in the red RAM plane
+    ///
+    /// The black and white plane is inverted, a set bit is white. `None` leaves the black and
+    /// white plane alone: red covers it.
+    pub(crate) fn plane_bits(self) -> (Option<bool>, bool) {
+        match self {
+            TriColor::White => (Some(true), false),
+            TriColor::Black => (Some(false), false),
+            TriColor::Red => (None, true),
+        }
+    }
+
+    /// Bytes filling both RAM planes with the color, as the buffers of
+    /// `Display1in54::bw` and `Display1in54::red` start out for white
+    pub(crate) fn plane_bytes(self) -> (u8, u8) {
+        let (bw, red) = self.plane_bits();
+        let byte = |bit: bool| if bit { 0xFF } else { 0x00 };
+        // a plane left alone is white
+        (byte(bw.unwrap_or(true)), byte(red))
+    }
+}
+
 impl Color {
     /// Get the color encoding of the color for one bit
     pub fn get_bit_value(self) -> u8 {
@@ -83,7 +121,7 @@ mod tests {
     // test all values aside from 0 and 1 which all should panic
     #[test]
     fn from_u8_panic() {
-        for val in 2..=u8::max_value() {
+        for val in 2..=u8::MAX {
             extern crate std;
             let result = std::panic::catch_unwind(|| Color::from(val));
             assert!(result.is_err());
@@ -0,0 +1,117 @@
+//! Controllers sharing the SSD1681 command set
+//!
+//! The SSD1680, SSD1608 and SSD1675B found on 2.13" and 2.9" panels understand the same
+//! commands as the SSD1681. They differ in how large a panel they drive, the size of their
+//! waveform LUT and a few values sent during [`init`](crate::driver::Ssd1681::init). The
+//! controller is picked with the last type parameter of [`driver::Ssd1681`](crate::driver::Ssd1681)
+//! and the panel size with [`with_interface`](crate::driver::Ssd1681::with_interface).
+//!
+//! Reading the OTP, the analog settings and the diagnostics are only available on the SSD1681,
+//! their registers differ between the controllers.
+
+use crate::flag;
+
+/// Limits and parameters of a controller
+pub trait Controller {
+    /// Source outputs, the widest panel in pixels
+    const MAX_WIDTH: u16;
+    /// Gate outputs, the tallest panel in pixels
+    const MAX_HEIGHT: u16;
+    /// Bytes in a waveform look up table
+    const LUT_LEN: usize;
+    /// Border Waveform Control sent by `init`
+    const BORDER_WAVEFORM: u8;
+    /// Temperature sensor selection sent by `init`, `None` if the controller has no such command
+    const TEMP_SENSOR: Option<u8>;
+    /// Step height and width of an Auto Write RAM pattern (0x46/0x47) covering the whole RAM
+    ///
+    /// `None` if the controller has no such command, solid fills are then sent byte by byte.
+    const AUTO_WRITE_STEP: Option<u8>;
+    /// Display Update Control 2 sequence of a full refresh
+    const UPDATE_FULL: u8;
+    /// Display Update Control 2 sequence of a partial refresh
+    const UPDATE_PARTIAL: u8;
+}
+
+/// Solomon Systech SSD1681, 200x200 (1.54")
+pub struct Ssd1681;
+
+impl Controller for Ssd1681 {
+    const MAX_WIDTH: u16 = 200;
+    const MAX_HEIGHT: u16 = 200;
+    const LUT_LEN: usize = 153;
+    const BORDER_WAVEFORM: u8 = flag::BORDER_WAVEFORM_FOLLOW_LUT | flag::BORDER_WAVEFORM_LUT1;
+    const TEMP_SENSOR: Option<u8> = Some(flag::INTERNAL_TEMP_SENSOR);
+    // 200 rows high, 200 pixels wide
+    const AUTO_WRITE_STEP: Option<u8> = Some(0x55);
+    const UPDATE_FULL: u8 = flag::DISPLAY_MODE_1;
+    const UPDATE_PARTIAL: u8 = flag::DISPLAY_MODE_2;
+}
+
+/// Solomon Systech SSD1680, up to 176x296 (2.13" and 2.9")
+pub struct Ssd1680;
+
+impl Controller for Ssd1680 {
+    const MAX_WIDTH: u16 = 176;
+    const MAX_HEIGHT: u16 = 296;
+    const LUT_LEN: usize = 153;
+    const BORDER_WAVEFORM: u8 = flag::BORDER_WAVEFORM_FOLLOW_LUT | flag::BORDER_WAVEFORM_LUT1;
+    const TEMP_SENSOR: Option<u8> = Some(flag::INTERNAL_TEMP_SENSOR);
+    // 296 rows high, 176 pixels wide
+    const AUTO_WRITE_STEP: Option<u8> = Some(0x65);
+    const UPDATE_FULL: u8 = flag::DISPLAY_MODE_1;
+    const UPDATE_PARTIAL: u8 = flag::DISPLAY_MODE_2;
+}
+
+/// Solomon Systech SSD1675B, up to 160x296 (2.13")
+pub struct Ssd1675b;
+
+impl Controller for Ssd1675b {
+    const MAX_WIDTH: u16 = 160;
+    const MAX_HEIGHT: u16 = 296;
+    const LUT_LEN: usize = 70;
+    const BORDER_WAVEFORM: u8 = 0x03;
+    const TEMP_SENSOR: Option<u8> = Some(flag::INTERNAL_TEMP_SENSOR);
+    const AUTO_WRITE_STEP: Option<u8> = None;
+    // no display modes, the waveform is the LUT written by the host and must not be replaced
+    // from OTP
+    const UPDATE_FULL: u8 = flag::DISPLAY_WITH_LUT_REGISTER;
+    const UPDATE_PARTIAL: u8 = flag::DISPLAY_WITH_LUT_REGISTER;
+}
+
+/// Solomon Systech SSD1608, up to 240x320 (1.54" and 2.9")
+pub struct Ssd1608;
+
+impl Controller for Ssd1608 {
+    const MAX_WIDTH: u16 = 240;
+    const MAX_HEIGHT: u16 = 320;
+    const LUT_LEN: usize = 30;
+    const BORDER_WAVEFORM: u8 = 0x33;
+    const TEMP_SENSOR: Option<u8> = None;
+    const AUTO_WRITE_STEP: Option<u8> = None;
+    // no display modes, the waveform is the LUT written by the host and must not be replaced
+    // from OTP
+    const UPDATE_FULL: u8 = flag::DISPLAY_WITH_LUT_REGISTER;
+    const UPDATE_PARTIAL: u8 = flag::DISPLAY_WITH_LUT_REGISTER;
+}
+
+/// Size of the panel connected to the controller, in pixels
+///
+/// The width does not have to be a multiple of 8, rows are padded to whole bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PanelSize {
+    /// Pixels per row, the source outputs used
+    pub width: u16,
+    /// Rows, the gate outputs used
+    pub height: u16,
+}
+
+impl PanelSize {
+    pub(crate) fn row_len(self) -> usize {
+        usize::from(self.width).div_ceil(8)
+    }
+
+    pub(crate) fn buffer_len(self) -> usize {
+        self.row_len() * usize::from(self.height)
+    }
+}
@@ -0,0 +1,362 @@
+//! Drawing straight into the controller RAM
+//!
+//! [`Ssd1681`] is a [`DrawTarget`] itself when its transport can read, so simple user
+//! interfaces need no frame buffer at all. Filled rectangles become RAM window writes, only
+//! the bytes they share with their surroundings at the left and right edge are read back
+//! first. Images that start and end on whole bytes are written row by row. Every other pixel
+//! is read from the RAM, changed and written back, consecutive pixels in the same byte share
+//! that.
+//!
+//! Drawing uses buffer coordinates, for rotations use
+//! [hardware rotation](crate::graphics::DisplayRotation::data_entry_mode). Show what was
+//! drawn with [`display_frame`](Ssd1681::display_frame).
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use crate::color::TriColor;
+use crate::controller::{self, Controller};
+use crate::driver::{FrameArea, Ssd1681};
+use crate::graphics::byte_mask;
+use crate::interface::{DisplayError, ReadData, ReadError, WriteOnlyDataCommand};
+use crate::pin::{BusyPin, ResetPin};
+use crate::ram::RamPlane;
+
+/// Bytes in the longest row any supported controller drives
+const MAX_ROW_LEN: usize = controller::Ssd1608::MAX_WIDTH as usize / 8;
+
+/// Rows of an edge column read back at once
+const COLUMN_CHUNK_SIZE: usize = 64;
+
+// Byte of the RAM held back while consecutive pixels change it
+struct Cached {
+    x: u32,
+    y: u32,
+    byte: u8,
+}
+
+impl<DI, BUSY, RST, C, const SHADOW_ROWS: usize> Ssd1681<DI, BUSY, RST, C, SHADOW_ROWS>
+where
+    DI: WriteOnlyDataCommand + ReadData,
+    BUSY: BusyPin,
+    RST: ResetPin,
+    C: Controller,
+{
+    fn set_pixel(
+        &mut self,
+        cache: &mut Option<Cached>,
+        plane: RamPlane,
+        point: Point,
+        bit: bool,
+    ) -> Result<(), ReadError> {
+        let (x, y) = (point.x as u32 & !7, point.y as u32);
+        let byte = match cache.take() {
+            Some(cached) if cached.x == x && cached.y == y => cached.byte,
+            other => {
+                *cache = other;
+                self.flush_pixels(cache, plane)?;
+                let mut byte = [0];
+                self.read_area(plane, byte_area(x, y, 1), &mut byte)?;
+                byte[0]
+            }
+        };
+        let mask = 0x80 >> (point.x as u32 % 8);
+        let byte = if bit { byte | mask } else { byte & !mask };
+        *cache = Some(Cached { x, y, byte });
+        Ok(())
+    }
+
+    fn flush_pixels(
+        &mut self,
+        cache: &mut Option<Cached>,
+        plane: RamPlane,
+    ) -> Result<(), DisplayError> {
+        match cache.take() {
+            Some(cached) => {
+                self.write_area(plane, byte_area(cached.x, cached.y, 1), &[cached.byte])
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Change the bits in `mask` of a column one byte wide
+    fn fill_column(
+        &mut self,
+        plane: RamPlane,
+        area: FrameArea,
+        mask: u8,
+        bit: bool,
+    ) -> Result<(), ReadError> {
+        let mut column = [0; COLUMN_CHUNK_SIZE];
+        let mut y = area.y;
+        while y < area.y + area.height {
+            let rows = (area.y + area.height - y).min(COLUMN_CHUNK_SIZE as u32);
+            let chunk = byte_area(area.x, y, rows);
+            let column = &mut column[..rows as usize];
+            self.read_area(plane, chunk, column)?;
+            for byte in column.iter_mut() {
+                *byte = if bit { *byte | mask } else { *byte & !mask };
+            }
+            self.write_area(plane, chunk, column)?;
+            y += rows;
+        }
+        Ok(())
+    }
+
+    fn fill_plane_area(
+        &mut self,
+        plane: RamPlane,
+        area: &Rectangle,
+        bit: bool,
+    ) -> Result<(), ReadError> {
+        let (y, height) = (area.top_left.y as u32, area.size.height);
+        let (left, right) = (
+            area.top_left.x as u32,
+            area.top_left.x as u32 + area.size.width,
+        );
+        let (inner_left, inner_right) = ((left + 7) & !7, right & !7);
+
+        if inner_left > inner_right {
+            // starts and ends inside of the same byte
+            let x = left & !7;
+            let area = byte_area(x, y, height);
+            return self.fill_column(plane, area, byte_mask(left - x, right - x), bit);
+        }
+        if inner_left < inner_right {
+            let inner = FrameArea {
+                x: inner_left,
+                y,
+                width: inner_right - inner_left,
+                height,
+            };
+            self.fill_area(plane, inner, if bit { 0xFF } else { 0x00 })?;
+        }
+        if left < inner_left {
+            let x = inner_left - 8;
+            self.fill_column(plane, byte_area(x, y, height), byte_mask(left - x, 8), bit)?;
+        }
+        if right > inner_right {
+            let area = byte_area(inner_right, y, height);
+            self.fill_column(plane, area, byte_mask(0, right - inner_right), bit)?;
+        }
+        Ok(())
+    }
+}
+
+fn byte_area(x: u32, y: u32, height: u32) -> FrameArea {
+    FrameArea {
+        x,
+        y,
+        width: 8,
+        height,
+    }
+}
+
+impl<DI, BUSY, RST, C, const SHADOW_ROWS: usize> Dimensions
+    for Ssd1681<DI, BUSY, RST, C, SHADOW_ROWS>
+where
+    DI: WriteOnlyDataCommand,
+    BUSY: BusyPin,
+    RST: ResetPin,
+    C: Controller,
+{
+    fn bounding_box(&self) -> Rectangle {
+        let size = self.size();
+        Rectangle::new(
+            Point::zero(),
+            Size::new(u32::from(size.width), u32::from(size.height)),
+        )
+    }
+}
+
+impl<DI, BUSY, RST, C, const SHADOW_ROWS: usize> DrawTarget
+    for Ssd1681<DI, BUSY, RST, C, SHADOW_ROWS>
+where
+    DI: WriteOnlyDataCommand + ReadData,
+    BUSY: BusyPin,
+    RST: ResetPin,
+    C: Controller,
+{
+    type Color = TriColor;
+    type Error = ReadError;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let bounds = self.bounding_box();
+        let (mut bw, mut red) = (None, None);
+        for Pixel(point, color) in pixels.into_iter() {
+            if !bounds.contains(point) {
+                continue;
+            }
+            let (bw_bit, red_bit) = color.plane_bits();
+            if let Some(bit) = bw_bit {
+                self.set_pixel(&mut bw, RamPlane::BlackWhite, point, bit)?;
+            }
+            self.set_pixel(&mut red, RamPlane::Red, point, red_bit)?;
+        }
+        self.flush_pixels(&mut bw, RamPlane::BlackWhite)?;
+        self.flush_pixels(&mut red, RamPlane::Red)?;
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let (x, width) = (area.top_left.x, area.size.width);
+        let row_len = width as usize / 8;
+        let aligned = x % 8 == 0 && width % 8 == 0 && row_len <= MAX_ROW_LEN;
+        if !aligned || area.is_zero_sized() || self.bounding_box().intersection(area) != *area {
+            let pixels = area.points().zip(colors).map(|(p, c)| Pixel(p, c));
+            return self.draw_iter(pixels);
+        }
+
+        let (mut bw, mut red, mut keep) = ([0; MAX_ROW_LEN], [0; MAX_ROW_LEN], [0; MAX_ROW_LEN]);
+        let (bw, red, keep) = (
+            &mut bw[..row_len],
+            &mut red[..row_len],
+            &mut keep[..row_len],
+        );
+        let mut colors = colors.into_iter();
+        for y in area.rows() {
+            for i in 0..row_len {
+                let (mut b, mut r, mut k) = (0, 0, 0);
+                for mask in (0..8).map(|bit| 0x80 >> bit) {
+                    let (bw_bit, red_bit) = colors.next().unwrap_or(TriColor::White).plane_bits();
+                    match bw_bit {
+                        Some(true) => b |= mask,
+                        Some(false) => {}
+                        None => k |= mask,
+                    }
+                    if red_bit {
+                        r |= mask;
+                    }
+                }
+                bw[i] = b;
+                red[i] = r;
+                keep[i] = k;
+            }
+
+            let row = FrameArea {
+                x: x as u32,
+                y: y as u32,
+                width,
+                height: 1,
+            };
+            if keep.iter().any(|&k| k != 0) {
+                // red pixels leave what the black and white plane holds
+                let mut stored = [0; MAX_ROW_LEN];
+                let stored = &mut stored[..row_len];
+                self.read_area(RamPlane::BlackWhite, row, stored)?;
+                for ((b, s), k) in bw.iter_mut().zip(stored.iter()).zip(keep.iter()) {
+                    *b = (*b & !k) | (s & k);
+                }
+            }
+            self.write_area(RamPlane::BlackWhite, row, bw)?;
+            self.write_area(RamPlane::Red, row, red)?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return Ok(());
+        }
+        let (bw_bit, red_bit) = color.plane_bits();
+        if let Some(bit) = bw_bit {
+            self.fill_plane_area(RamPlane::BlackWhite, &area, bit)?;
+        }
+        self.fill_plane_area(RamPlane::Red, &area, red_bit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::iter::repeat;
+    use std::{vec, vec::Vec};
+
+    use crate::cmd;
+    use crate::color::TriColor;
+    use crate::mock::Sim;
+    use embedded_graphics::{prelude::*, primitives::Rectangle};
+
+    // Data of every occurrence of `command`
+    fn all_data(sim: &Sim, command: u8) -> Vec<Vec<u8>> {
+        sim.commands()
+            .into_iter()
+            .filter(|(c, _)| *c == command)
+            .map(|(_, data)| data)
+            .collect()
+    }
+
+    #[test]
+    fn fill_solid_writes_windows() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        sim.clear();
+
+        let area = Rectangle::new(Point::new(8, 2), Size::new(16, 3));
+        ssd1681.fill_solid(&area, TriColor::Black).unwrap();
+        assert_eq!(sim.data_for(cmd::SET_RAMXPOS).unwrap(), [1, 2]);
+        assert_eq!(sim.data_for(cmd::SET_RAMYPOS).unwrap(), [2, 0, 4, 0]);
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap(), [0x00; 6]);
+        assert_eq!(sim.data_for(cmd::WRITE_RED_DATA).unwrap(), [0x00; 6]);
+        assert_eq!(sim.data_for(cmd::READ_RAM), None);
+
+        sim.clear();
+        sim.respond(cmd::READ_RAM, &[0x00, 0x00]);
+        let area = Rectangle::new(Point::new(4, 0), Size::new(8, 1));
+        ssd1681.fill_solid(&area, TriColor::Red).unwrap();
+        assert_eq!(all_data(&sim, cmd::WRITE_BW_DATA), Vec::<Vec<u8>>::new());
+        assert_eq!(
+            all_data(&sim, cmd::WRITE_RED_DATA),
+            [vec![0x0F], vec![0xF0]]
+        );
+    }
+
+    #[test]
+    fn fill_contiguous_writes_rows() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        sim.clear();
+        sim.respond(cmd::READ_RAM, &[0x00, 0xAA]);
+
+        let area = Rectangle::new(Point::new(8, 0), Size::new(8, 2));
+        let colors = repeat(TriColor::Black)
+            .take(8)
+            .chain(repeat(TriColor::Red).take(4))
+            .chain(repeat(TriColor::White).take(4));
+        ssd1681.fill_contiguous(&area, colors).unwrap();
+
+        assert_eq!(all_data(&sim, cmd::READ_RAM).len(), 1);
+        assert_eq!(all_data(&sim, cmd::WRITE_BW_DATA), [vec![0x00], vec![0xAF]]);
+        assert_eq!(
+            all_data(&sim, cmd::WRITE_RED_DATA),
+            [vec![0x00], vec![0xF0]]
+        );
+    }
+
+    #[test]
+    fn pixels_are_read_back() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        sim.clear();
+        sim.respond(cmd::READ_RAM, &[0x00, 0x0F]);
+
+        let pixels = [
+            Pixel(Point::new(4, 0), TriColor::Black),
+            Pixel(Point::new(5, 0), TriColor::Black),
+            Pixel(Point::new(-1, 0), TriColor::Black),
+        ];
+        ssd1681.draw_iter(pixels).unwrap();
+
+        assert_eq!(all_data(&sim, cmd::READ_RAM).len(), 2);
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap(), [0x03]);
+        assert_eq!(sim.data_for(cmd::WRITE_RED_DATA).unwrap(), [0x03]);
+    }
+}
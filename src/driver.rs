@@ -1,155 +1,1113 @@
 //! Driver for interacting with SSD1681 display driver
-use core::fmt::{Debug};
+use core::convert::Infallible;
+use core::fmt::Debug;
+use core::marker::PhantomData;
 
+use crate::controller::{self, Controller, PanelSize};
+use crate::interface::{
+    Commands, DisplayError, ReadCommands, ReadData, ReadError, SpiInterface, WriteOnlyDataCommand,
+};
+use crate::otp::{DisplayOption, OtpUnlock, UserId, DISPLAY_OPTION_LEN, USER_ID_LEN};
+use crate::pin::{BusyPin, BusyWith, ResetPin};
+use crate::ram::{crc16, crc16_update, RamPlane, RamShadow, Upload, VerifyError};
+use crate::refresh::{Clock, RefreshError, RefreshHistory, RefreshKind, RefreshPolicy, TooSoon};
+use crate::status::{Diagnostic, Status, VciLevel, CHIP_ID};
+use crate::typestate::TransitionError;
+use crate::voltage::{
+    BoosterSoftStart, GateVoltage, SourceHighVoltage, SourceLowVoltage, VcomVoltage,
+};
+use crate::{cmd, color, flag, HEIGHT, WIDTH};
 use embedded_hal::delay::DelayNs;
-use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::digital::OutputPin;
 use embedded_hal::spi::SpiDevice;
-use crate::interface::DisplayInterface;
-use crate::{cmd, color, flag, HEIGHT, WIDTH};
+
+/// Number of bytes in a waveform look up table of the SSD1681
+pub const LUT_LEN: usize = controller::Ssd1681::LUT_LEN;
+
+/// Bytes filled per call by [`Ssd1681::stream_frame`]
+const STREAM_CHUNK_SIZE: usize = 128;
+
+/// Most bytes read back in one transaction, whole rows are read at a time
+const READ_CHUNK_SIZE: usize = 128;
+
+/// The 1.54" panel driven by [`Ssd1681::new`]
+pub(crate) const PANEL_1IN54: PanelSize = PanelSize {
+    width: WIDTH as u16,
+    height: HEIGHT as u16,
+};
+
+/// Order in which the controller fills its RAM with incoming bytes
+///
+/// The address counter always moves in X (one byte, 8 pixels, at a time) and wraps to the
+/// next line in Y. Decrementing X mirrors the frame horizontally and decrementing Y mirrors it
+/// vertically, so a buffer in draw order can be shown rotated by 180 degrees without touching
+/// its pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DataEntryMode {
+    /// Y decrement, X decrement
+    DecrYDecrX,
+    /// Y decrement, X increment
+    DecrYIncrX,
+    /// Y increment, X decrement
+    IncrYDecrX,
+    /// Y increment, X increment
+    #[default]
+    IncrYIncrX,
+}
+
+impl DataEntryMode {
+    fn flag(self) -> u8 {
+        match self {
+            DataEntryMode::DecrYDecrX => flag::DATA_ENTRY_DECRY_DECRX,
+            DataEntryMode::DecrYIncrX => flag::DATA_ENTRY_DECRY_INCRX,
+            DataEntryMode::IncrYDecrX => flag::DATA_ENTRY_INCRY_DECRX,
+            DataEntryMode::IncrYIncrX => flag::DATA_ENTRY_INCRY_INCRX,
+        }
+    }
+
+    fn increments_x(self) -> bool {
+        matches!(self, DataEntryMode::DecrYIncrX | DataEntryMode::IncrYIncrX)
+    }
+
+    fn increments_y(self) -> bool {
+        matches!(self, DataEntryMode::IncrYDecrX | DataEntryMode::IncrYIncrX)
+    }
+}
+
+/// Gate scanning bits of the Driver Output Control command
+///
+/// [`bottom_to_top`](Self::bottom_to_top) mirrors the panel vertically in the controller
+/// without touching RAM.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GateScan {
+    /// GD: start scanning at G1 instead of G0
+    pub first_gate_g1: bool,
+    /// SM: interlaced scan, even gates before odd gates
+    pub interlaced: bool,
+    /// TB: scan from the last gate to G0
+    pub bottom_to_top: bool,
+}
+
+impl GateScan {
+    fn flag(self) -> u8 {
+        let mut flag = 0;
+        if self.first_gate_g1 {
+            flag |= flag::GATE_SCAN_GD;
+        }
+        if self.interlaced {
+            flag |= flag::GATE_SCAN_SM;
+        }
+        if self.bottom_to_top {
+            flag |= flag::GATE_SCAN_TB;
+        }
+        flag
+    }
+}
+
+/// Settings for [`Ssd1681::deghost`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deghost {
+    /// Number of black, white and red rounds
+    pub cycles: u8,
+    /// Include a red frame in every round, for tri-color panels
+    pub red: bool,
+    /// Deep sleep mode to enter when done, `None` to stay awake
+    pub sleep: Option<DeepSleepMode>,
+}
+
+impl Default for Deghost {
+    /// Three rounds with red and no sleep
+    fn default() -> Self {
+        Deghost {
+            cycles: 3,
+            red: true,
+            sleep: None,
+        }
+    }
+}
+
+/// Rectangle of a frame buffer, in pixels
+///
+/// `x` and `width` have to be multiples of 8 since the RAM packs 8 horizontal pixels per byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameArea {
+    /// Left edge
+    pub x: u32,
+    /// Top edge
+    pub y: u32,
+    /// Width
+    pub width: u32,
+    /// Height
+    pub height: u32,
+}
+
+/// Deep sleep mode of the controller
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeepSleepMode {
+    /// Mode 1, the RAM keeps its content
+    #[default]
+    RetainRam,
+    /// Mode 2, the RAM content is lost
+    DiscardRam,
+}
+
+impl DeepSleepMode {
+    fn flag(self) -> u8 {
+        match self {
+            DeepSleepMode::RetainRam => flag::DEEP_SLEEP_MODE_1,
+            DeepSleepMode::DiscardRam => flag::DEEP_SLEEP_MODE_2,
+        }
+    }
+}
 
 /// A configured display with a hardware interface.
-pub struct Ssd1681<SPI, CS, BUSY, DC, RST> {
-    interface: DisplayInterface<SPI, CS, BUSY, DC, RST>,
+///
+/// `DI` is the transport to the controller, any [`WriteOnlyDataCommand`] like the
+/// `SPIInterface` of `display-interface-spi` or the crate's own [`SpiInterface`]. Reading from
+/// the controller needs a transport that also implements [`ReadData`].
+///
+/// `BUSY` and `RST` can be [`NoBusy`](crate::pin::NoBusy) and [`NoPin`](crate::pin::NoPin) on
+/// modules that do not break them out.
+///
+/// `C` is the [`Controller`] on the panel, the SSD1681 unless created with
+/// [`with_interface`](Self::with_interface).
+///
+/// `SHADOW_ROWS` is the number of rows the RAM shadow tracks, 0 and no shadow unless created
+/// with [`with_shadow`](Self::with_shadow).
+pub struct Ssd1681<DI, BUSY, RST, C = controller::Ssd1681, const SHADOW_ROWS: usize = 0> {
+    interface: DI,
+    /// Low for busy, Wait until display is ready!
+    busy: BUSY,
+    /// Pin for Resetting
+    rst: RST,
+    size: PanelSize,
+    data_entry_mode: DataEntryMode,
+    gate_scan: GateScan,
+    bw_shadow: RamShadow<SHADOW_ROWS>,
+    red_shadow: RamShadow<SHADOW_ROWS>,
+    refresh_policy: RefreshPolicy,
+    refresh_history: RefreshHistory,
+    _controller: PhantomData<C>,
 }
 
-impl<SPI, CS, BUSY, DC, RST> Ssd1681<SPI, CS, BUSY, DC, RST>
+impl<SPI, DC, BUSY, RST> Ssd1681<SpiInterface<SPI, DC>, BUSY, RST>
 where
     SPI: SpiDevice,
-    CS: OutputPin,
-    CS::Error: Debug,
-    BUSY: InputPin,
     DC: OutputPin,
     DC::Error: Debug,
-    RST: OutputPin,
-    RST::Error: Debug,
+    BUSY: BusyPin,
+    RST: ResetPin,
 {
-    /// Create and initialize the display driver
+    /// Create and initialize the display driver on a 4-wire SPI bus
+    ///
+    /// Chip select is handled by the [`SpiDevice`].
     pub fn new<DELAY: DelayNs>(
-        spi: &mut SPI,
-        cs: CS,
+        spi: SPI,
         busy: BUSY,
         dc: DC,
         rst: RST,
         delay: &mut DELAY,
-    ) -> Result<Self, SPI::Error>
+    ) -> Result<Self, DisplayError>
     where
         Self: Sized,
     {
-        let interface = DisplayInterface::new(cs, busy, dc, rst);
-        let mut ssd1681 = Ssd1681 { interface };
-        ssd1681.init(spi, delay)?;
-        Ok(ssd1681)
+        let interface = SpiInterface::new(spi, dc);
+        Ssd1681::with_interface(interface, busy, rst, PANEL_1IN54, delay)
     }
+}
 
-    /// Initialise the controller
-    pub fn init<DELAY: DelayNs>(
-        &mut self,
-        spi: &mut SPI,
-        delay: &mut DELAY,
-    ) -> Result<(), SPI::Error> {
-        self.interface.reset(delay);
-        self.interface.cmd(spi, cmd::SW_RESET)?;
-        self.interface.wait_until_idle();
+impl<DI, BUSY, RST, const SHADOW_ROWS: usize>
+    Ssd1681<DI, BUSY, RST, controller::Ssd1681, SHADOW_ROWS>
+where
+    DI: WriteOnlyDataCommand,
+    BUSY: BusyPin,
+    RST: ResetPin,
+{
+    // The OTP, analog and status registers below are laid out for the SSD1681 only
 
+    /// Write the display option register
+    ///
+    /// The register is burned into OTP by the next OTP program command.
+    pub fn write_register_for_display_option(
+        &mut self,
+        option: &DisplayOption,
+        _unlock: &OtpUnlock,
+    ) -> Result<(), DisplayError> {
         self.interface
-            .cmd_with_data(spi, cmd::DRIVER_CONTROL, &[HEIGHT - 1, 0x00, 0x00])?;
+            .cmd_with_data(cmd::WRITE_DISPLAY_OPTION, &option.to_register_bytes())
+    }
 
+    /// Write the user ID register
+    ///
+    /// The register is burned into OTP by the next OTP program command.
+    pub fn write_user_id(
+        &mut self,
+        user_id: &UserId,
+        _unlock: &OtpUnlock,
+    ) -> Result<(), DisplayError> {
         self.interface
-            .cmd_with_data(spi, cmd::DATA_ENTRY_MODE, &[flag::DATA_ENTRY_INCRY_INCRX])?;
+            .cmd_with_data(cmd::WRITE_USER_ID, user_id.as_bytes())
+    }
 
-        self.use_full_frame(spi)?;
+    /// Set the gate driving voltage VGH
+    pub fn set_gate_voltage(&mut self, vgh: GateVoltage) -> Result<(), DisplayError> {
+        self.interface
+            .cmd_with_data(cmd::GATE_VOLTAGE, &[vgh.register()])
+    }
 
+    /// Set the source driving voltages VSH1, VSH2 and VSL
+    pub fn set_source_voltage(
+        &mut self,
+        vsh1: SourceHighVoltage,
+        vsh2: SourceHighVoltage,
+        vsl: SourceLowVoltage,
+    ) -> Result<(), DisplayError> {
         self.interface.cmd_with_data(
-            spi,
-            cmd::BORDER_WAVEFORM_CONTROL,
-            &[flag::BORDER_WAVEFORM_FOLLOW_LUT | flag::BORDER_WAVEFORM_LUT1],
-        )?;
+            cmd::SOURCE_VOLTAGE,
+            &[vsh1.register(), vsh2.register(), vsl.register()],
+        )
+    }
+
+    /// Set the VCOM voltage
+    pub fn set_vcom(&mut self, vcom: VcomVoltage) -> Result<(), DisplayError> {
+        self.interface
+            .cmd_with_data(cmd::WRITE_VCOM, &[vcom.register()])
+    }
+
+    /// Set the booster soft start phases
+    pub fn set_booster_soft_start(
+        &mut self,
+        soft_start: BoosterSoftStart,
+    ) -> Result<(), DisplayError> {
+        self.interface
+            .cmd_with_data(cmd::BOOSTER_SOFT_START, &soft_start.register())
+    }
+}
+
+impl<DI, BUSY, RST, const SHADOW_ROWS: usize>
+    Ssd1681<DI, BUSY, RST, controller::Ssd1681, SHADOW_ROWS>
+where
+    DI: WriteOnlyDataCommand + ReadData,
+    BUSY: BusyPin,
+    RST: ResetPin,
+{
+    /// Let the controller calculate the CRC of its OTP content and read it back
+    pub fn otp_crc(&mut self) -> Result<u16, ReadError> {
+        self.interface.cmd(cmd::CRC_CALCULATION)?;
+        self.wait_for(BusyWith::Command);
+
+        let mut value = [0; 2];
+        self.interface.cmd_read(cmd::CRC_STATUS_READ, &mut value)?;
+        Ok(u16::from_be_bytes(value))
+    }
 
+    /// Read the display option stored in OTP
+    pub fn read_display_option(&mut self) -> Result<DisplayOption, ReadError> {
+        let mut bytes = [0; DISPLAY_OPTION_LEN];
         self.interface
-            .cmd_with_data(spi, cmd::TEMP_CONTROL, &[flag::INTERNAL_TEMP_SENSOR])?;
+            .cmd_read(cmd::READ_DISPLAY_OPTION, &mut bytes)?;
+        Ok(DisplayOption::from_bytes(&bytes))
+    }
+
+    /// Read the user ID stored in OTP
+    pub fn read_user_id(&mut self) -> Result<UserId, ReadError> {
+        let mut bytes = [0; USER_ID_LEN];
+        self.interface.cmd_read(cmd::READ_USER_ID, &mut bytes)?;
+        Ok(UserId(bytes))
+    }
+
+    /// Read the status bit register
+    pub fn status(&mut self) -> Result<Status, ReadError> {
+        let mut value = [0];
+        self.interface.cmd_read(cmd::STATUS_BIT_READ, &mut value)?;
+        Ok(Status::from_byte(value[0]))
+    }
 
-        self.interface.wait_until_idle();
+    /// Check if the booster reaches the high voltages needed to drive the panel
+    pub fn detect_hv_ready(&mut self) -> Result<bool, ReadError> {
+        self.with_clock_and_analog(|ssd1681| {
+            // no cool down loops
+            ssd1681
+                .interface
+                .cmd_with_data(cmd::HV_READY_DETECTION, &[0x00])?;
+            ssd1681.wait_for(BusyWith::Command);
+            Ok(ssd1681.status()?.hv_ready)
+        })
+    }
+
+    /// Check if VCI is below `level`
+    pub fn detect_vci_low(&mut self, level: VciLevel) -> Result<bool, ReadError> {
+        self.with_clock_and_analog(|ssd1681| {
+            ssd1681
+                .interface
+                .cmd_with_data(cmd::VCI_DETECTION, &[level.flag()])?;
+            ssd1681.wait_for(BusyWith::Command);
+            Ok(ssd1681.status()?.vci_low)
+        })
+    }
+
+    /// Run all detections and report why the panel might not update
+    pub fn self_test(&mut self) -> Result<Diagnostic, ReadError> {
+        let status = self.status()?;
+        let responding = status.chip_id == CHIP_ID;
+        if !responding {
+            // the detections would wait for a controller that is not there
+            return Ok(Diagnostic {
+                status,
+                responding,
+                hv_ready: false,
+                vci_ok: false,
+            });
+        }
+
+        let vci_ok = !self.detect_vci_low(VciLevel::default())?;
+        let hv_ready = self.detect_hv_ready()?;
+        Ok(Diagnostic {
+            status,
+            responding,
+            hv_ready,
+            vci_ok,
+        })
+    }
+
+    // The detections and OTP programming need the clock and the analog block running
+    pub(crate) fn with_clock_and_analog<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, ReadError>,
+    ) -> Result<T, ReadError> {
+        self.activate(flag::ENABLE_CLOCK_ANALOG)?;
+        let result = f(self)?;
+        self.activate(flag::DISABLE_CLOCK_ANALOG)?;
+        Ok(result)
+    }
+
+    fn activate(&mut self, sequence: u8) -> Result<(), DisplayError> {
+        self.run_sequence(sequence)?;
+        self.wait_for(BusyWith::Command);
         Ok(())
     }
+}
 
-    /// Update the whole BW buffer on the display driver
-    pub fn update_bw_frame(&mut self, spi: &mut SPI, buffer: &[u8]) -> Result<(), SPI::Error> {
-        self.use_full_frame(spi)?;
+impl<DI, BUSY, RST, C> Ssd1681<DI, BUSY, RST, C>
+where
+    DI: WriteOnlyDataCommand,
+    BUSY: BusyPin,
+    RST: ResetPin,
+    C: Controller,
+{
+    /// Create and initialize the display driver for a panel of `size` on controller `C`
+    ///
+    /// ```text
+    /// let interface = SPIInterface::new(spi, dc);
+    /// let epd = Ssd1681::<_, _, _, Ssd1680>::with_interface(
+    ///     interface, busy, rst, PanelSize { width: 128, height: 296 }, &mut delay)?;
+    /// ```
+    ///
+    /// Panics if the panel is larger than the controller supports.
+    pub fn with_interface<DELAY: DelayNs>(
+        interface: DI,
+        busy: BUSY,
+        rst: RST,
+        size: PanelSize,
+        delay: &mut DELAY,
+    ) -> Result<Self, DisplayError> {
+        let mut ssd1681 = Ssd1681::new_uninit(interface, busy, rst, size);
+        ssd1681.init(delay)?;
+        Ok(ssd1681)
+    }
+
+    /// Create the display driver without talking to the controller
+    pub(crate) fn new_uninit(interface: DI, busy: BUSY, rst: RST, size: PanelSize) -> Self {
+        assert!(size.width > 0 && size.width <= C::MAX_WIDTH);
+        assert!(size.height > 0 && size.height <= C::MAX_HEIGHT);
+        Ssd1681 {
+            interface,
+            busy,
+            rst,
+            size,
+            data_entry_mode: DataEntryMode::default(),
+            gate_scan: GateScan::default(),
+            bw_shadow: RamShadow::new(),
+            red_shadow: RamShadow::new(),
+            refresh_policy: RefreshPolicy::default(),
+            refresh_history: RefreshHistory::default(),
+            _controller: PhantomData,
+        }
+    }
+
+    /// Skip uploading rows the controller RAM already holds
+    ///
+    /// The driver remembers a CRC of every row it wrote to the RAM, 4 bytes per row of the
+    /// panel for both planes, and full frame updates only send the rows whose CRC changed.
+    /// `ROWS` is the number of rows tracked and has to cover the height of the panel:
+    ///
+    /// ```text
+    /// let epd = Ssd1681::new(spi, busy, dc, rst, &mut delay)?.with_shadow::<200>();
+    /// ```
+    ///
+    /// A row changed in a way that keeps its CRC is not sent, changes of up to three bits or
+    /// within 16 consecutive bits always change it. Leave it off when every frame has to reach
+    /// the panel no matter what, or check with [`verify_frame`](Ssd1681::verify_frame).
+    /// Without it the driver keeps no shadow at all.
+    pub fn with_shadow<const ROWS: usize>(self) -> Ssd1681<DI, BUSY, RST, C, ROWS> {
+        assert!(usize::from(self.size.height) <= ROWS);
+        Ssd1681 {
+            interface: self.interface,
+            busy: self.busy,
+            rst: self.rst,
+            size: self.size,
+            data_entry_mode: self.data_entry_mode,
+            gate_scan: self.gate_scan,
+            bw_shadow: RamShadow::new(),
+            red_shadow: RamShadow::new(),
+            refresh_policy: self.refresh_policy,
+            refresh_history: self.refresh_history,
+            _controller: PhantomData,
+        }
+    }
+}
+
+impl<DI, BUSY, RST, C, const SHADOW_ROWS: usize> Ssd1681<DI, BUSY, RST, C, SHADOW_ROWS>
+where
+    DI: WriteOnlyDataCommand,
+    BUSY: BusyPin,
+    RST: ResetPin,
+    C: Controller,
+{
+    /// Size of the panel
+    pub fn size(&self) -> PanelSize {
+        self.size
+    }
+
+    /// The transport to the controller, for settings like
+    /// [`SpiInterface::set_max_chunk_size`]
+    pub fn interface_mut(&mut self) -> &mut DI {
+        &mut self.interface
+    }
+
+    /// Tear the driver down and return the transport and the pins
+    ///
+    /// The controller is left as it is, see [`release_sleeping`](Self::release_sleeping) to
+    /// leave the panel in deep sleep.
+    pub fn release(self) -> (DI, BUSY, RST) {
+        (self.interface, self.busy, self.rst)
+    }
+
+    /// Put the controller into deep sleep, then return the transport and the pins
+    ///
+    /// If the controller can not be put to sleep the driver is handed back in the error.
+    #[allow(clippy::result_large_err)]
+    pub fn release_sleeping(
+        mut self,
+        mode: DeepSleepMode,
+    ) -> Result<(DI, BUSY, RST), TransitionError<Self>> {
+        match self.sleep(mode) {
+            Ok(()) => Ok(self.release()),
+            Err(error) => Err(TransitionError {
+                driver: self,
+                error,
+            }),
+        }
+    }
+
+    /// Initialise the controller
+    pub fn init<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        self.invalidate_shadow();
+        self.rst.reset(delay);
+        self.interface.cmd(cmd::SW_RESET)?;
+        self.wait_for(BusyWith::Reset);
+
+        self.send_driver_control()?;
+
+        self.interface
+            .cmd_with_data(cmd::DATA_ENTRY_MODE, &[self.data_entry_mode.flag()])?;
+
+        self.use_full_frame()?;
+
+        self.interface
+            .cmd_with_data(cmd::BORDER_WAVEFORM_CONTROL, &[C::BORDER_WAVEFORM])?;
+
+        if let Some(sensor) = C::TEMP_SENSOR {
+            self.interface.cmd_with_data(cmd::TEMP_CONTROL, &[sensor])?;
+        }
+
+        self.wait_until_idle();
+        Ok(())
+    }
+
+    /// Set the order in which frames are written into the controller RAM
+    ///
+    /// The mode is kept across [`init`](Self::init) and every following frame update uses a
+    /// RAM window and counter matching it.
+    pub fn set_data_entry_mode(&mut self, mode: DataEntryMode) -> Result<(), DisplayError> {
+        // the RAM layout of the buffers changes
+        self.invalidate_shadow();
+        self.data_entry_mode = mode;
         self.interface
-            .cmd_with_data(spi, cmd::WRITE_BW_DATA, &buffer)
+            .cmd_with_data(cmd::DATA_ENTRY_MODE, &[mode.flag()])
+    }
+
+    /// Get the current data entry mode
+    pub fn data_entry_mode(&self) -> DataEntryMode {
+        self.data_entry_mode
+    }
+
+    /// Set the gate scanning order of the panel
+    ///
+    /// Like the data entry mode it is kept across [`init`](Self::init).
+    pub fn set_gate_scan(&mut self, gate_scan: GateScan) -> Result<(), DisplayError> {
+        self.gate_scan = gate_scan;
+        self.send_driver_control()
+    }
+
+    /// Get the current gate scanning order
+    pub fn gate_scan(&self) -> GateScan {
+        self.gate_scan
+    }
+
+    /// Update the whole BW buffer on the display driver
+    ///
+    /// With [`with_shadow`](Self::with_shadow) only the rows that changed since the last
+    /// update are sent, or nothing if the frame is the same.
+    pub fn update_bw_frame(&mut self, buffer: &[u8]) -> Result<(), DisplayError> {
+        self.update_plane(RamPlane::BlackWhite, buffer)
     }
 
     /// Update the whole Red buffer on the display driver
-    pub fn update_red_frame(&mut self, spi: &mut SPI, buffer: &[u8]) -> Result<(), SPI::Error> {
-        self.use_full_frame(spi)?;
+    ///
+    /// Like [`update_bw_frame`](Self::update_bw_frame) only changed rows are sent with
+    /// [`with_shadow`](Self::with_shadow).
+    pub fn update_red_frame(&mut self, buffer: &[u8]) -> Result<(), DisplayError> {
+        self.update_plane(RamPlane::Red, buffer)
+    }
+
+    /// Update only `area` of the BW buffer on the display driver
+    ///
+    /// `buffer` is the whole frame, the rest of it has to be in the RAM already.
+    pub fn update_bw_area(&mut self, buffer: &[u8], area: FrameArea) -> Result<(), DisplayError> {
+        self.update_plane_area(RamPlane::BlackWhite, buffer, area)
+    }
+
+    /// Update only `area` of the Red buffer on the display driver
+    ///
+    /// `buffer` is the whole frame, the rest of it has to be in the RAM already.
+    pub fn update_red_area(&mut self, buffer: &[u8], area: FrameArea) -> Result<(), DisplayError> {
+        self.update_plane_area(RamPlane::Red, buffer, area)
+    }
+
+    /// Update the whole buffer of `plane` from a source that does not fit into memory
+    ///
+    /// `fill` is called with the offset into the frame of every chunk to fill, in order. As
+    /// the frame is never in memory as a whole, the next update of the plane sends it whole.
+    pub fn stream_frame<E>(
+        &mut self,
+        plane: RamPlane,
+        mut fill: impl FnMut(usize, &mut [u8]) -> Result<(), E>,
+    ) -> Result<(), E>
+    where
+        E: From<DisplayError>,
+    {
+        self.shadow(plane).invalidate();
+        self.use_full_frame()?;
+        self.interface.cmd(plane.write_command())?;
+
+        let mut buffer = [0; STREAM_CHUNK_SIZE];
+        let len = self.size.buffer_len();
+        let mut offset = 0;
+        while offset < len {
+            let chunk = &mut buffer[..(len - offset).min(STREAM_CHUNK_SIZE)];
+            fill(offset, chunk)?;
+            if self.data_entry_mode.increments_x() {
+                self.interface.data(chunk)?;
+            } else {
+                self.interface.data_mapped(chunk, u8::reverse_bits)?;
+            }
+            offset += chunk.len();
+        }
+        Ok(())
+    }
+
+    /// Write `data` into `area` of the RAM of `plane`
+    ///
+    /// Unlike [`update_bw_area`](Self::update_bw_area), `data` only holds the rows of the area,
+    /// each `area.width / 8` bytes long. As with [`stream_frame`](Self::stream_frame) the next
+    /// update of the plane sends it whole.
+    pub fn write_area(
+        &mut self,
+        plane: RamPlane,
+        area: FrameArea,
+        data: &[u8],
+    ) -> Result<(), DisplayError> {
+        self.assert_area(area);
+        assert_eq!(data.len(), (area.width / 8 * area.height) as usize);
+
+        self.shadow(plane).invalidate();
+        self.use_area(area)?;
+        self.write_ram(plane.write_command(), data)
+    }
+
+    /// Fill `area` of the RAM of `plane` with `value`
+    pub fn fill_area(
+        &mut self,
+        plane: RamPlane,
+        area: FrameArea,
+        value: u8,
+    ) -> Result<(), DisplayError> {
+        self.assert_area(area);
+
+        let value = if self.data_entry_mode.increments_x() {
+            value
+        } else {
+            value.reverse_bits()
+        };
+        self.shadow(plane).invalidate();
+        self.use_area(area)?;
+        self.interface.cmd(plane.write_command())?;
         self.interface
-            .cmd_with_data(spi, cmd::WRITE_RED_DATA, &buffer)
+            .data_x_times(value, area.width / 8 * area.height)
+    }
+
+    /// Forget what the controller RAM holds, the next updates send whole frames
+    ///
+    /// Needed after talking to the controller behind the driver's back.
+    pub fn invalidate_shadow(&mut self) {
+        self.bw_shadow.invalidate();
+        self.red_shadow.invalidate();
     }
 
     /// Start an update of the whole display
-    pub fn display_frame(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
-        self.interface
-            .cmd_with_data(spi, cmd::UPDATE_DISPLAY_CTRL2, &[flag::DISPLAY_MODE_1])?;
-        self.interface.cmd(spi, cmd::MASTER_ACTIVATE)?;
+    pub fn display_frame(&mut self) -> Result<(), DisplayError> {
+        self.start_refresh()?;
+        self.wait_until_idle();
+
+        Ok(())
+    }
 
-        self.interface.wait_until_idle();
+    /// Update the whole display with the partial update waveform (display mode 2)
+    ///
+    /// Faster and without flashing on panels whose LUT supports it, but ghosting builds up
+    /// and a regular [`display_frame`](Self::display_frame) is needed now and then. Runs
+    /// [`Controller::UPDATE_PARTIAL`], controllers without display modes refresh with whatever
+    /// LUT was written.
+    pub fn display_partial_frame(&mut self) -> Result<(), DisplayError> {
+        self.activate_refresh(RefreshKind::Partial)?;
+        self.refresh_history.record_unstamped(RefreshKind::Partial);
+        self.wait_until_idle();
 
         Ok(())
     }
 
-    /// Make the whole black and white frame on the display driver white
-    pub fn clear_bw_frame(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
-        self.use_full_frame(spi)?;
+    /// Set the rules [`refresh`](Self::refresh) follows
+    pub fn set_refresh_policy(&mut self, policy: RefreshPolicy) {
+        self.refresh_policy = policy;
+    }
 
-        // TODO: allow non-white background color
-        let color = color::Color::White.get_byte_value();
+    /// The rules [`refresh`](Self::refresh) follows
+    pub fn refresh_policy(&self) -> RefreshPolicy {
+        self.refresh_policy
+    }
 
-        self.interface.cmd(spi, cmd::WRITE_BW_DATA)?;
+    /// Refresh the display following the [`RefreshPolicy`] and wait until it is done
+    ///
+    /// A partial refresh is turned into a full one when the policy asks for it, the refresh
+    /// that was done is returned. A refresh that comes too soon sleeps on `delay` with
+    /// [`TooSoon::Defer`]. Refreshes started with [`display_frame`](Self::display_frame) and
+    /// friends count as done at the time this reads from `clock`.
+    pub fn refresh<CLOCK: Clock, DELAY: DelayNs>(
+        &mut self,
+        clock: &mut CLOCK,
+        delay: &mut DELAY,
+        requested: RefreshKind,
+    ) -> Result<RefreshKind, RefreshError<DisplayError>> {
+        let policy = self.refresh_policy;
+        let mut now = clock.now_ms();
+        self.refresh_history.stamp(now);
+        loop {
+            let wait = self.refresh_history.wait_ms(&policy, now);
+            if wait == 0 {
+                break;
+            }
+            match policy.too_soon {
+                TooSoon::Reject => return Err(RefreshError::TooSoon { remaining_ms: wait }),
+                TooSoon::Defer => {
+                    delay.delay_ms(wait.min(u64::from(u32::MAX)) as u32);
+                    now = clock.now_ms();
+                }
+            }
+        }
+
+        let kind = self.refresh_history.kind(&policy, requested, now);
+        self.activate_refresh(kind)?;
+        self.refresh_history.record(kind, now);
+        self.wait_until_idle();
+        Ok(kind)
+    }
+
+    /// Start an update of the whole display without waiting for it to finish
+    ///
+    /// Use [`poll_refresh`](Self::poll_refresh) or [`is_busy`](Self::is_busy) to find out when
+    /// the controller is done. It must not be sent anything before that.
+    pub fn start_refresh(&mut self) -> Result<(), DisplayError> {
+        self.activate_refresh(RefreshKind::Full)?;
+        self.refresh_history.record_unstamped(RefreshKind::Full);
+        Ok(())
+    }
+
+    /// Check if the controller is busy
+    ///
+    /// Without a BUSY pin ([`NoBusy`](crate::pin::NoBusy)) this waits until the controller has to
+    /// be done and returns `false`.
+    pub fn is_busy(&mut self) -> bool {
+        self.busy.is_busy()
+    }
+
+    /// Complete a refresh started with [`start_refresh`](Self::start_refresh)
+    ///
+    /// Returns [`nb::Error::WouldBlock`] while the controller is busy, so it can be polled from
+    /// a main loop or waited for with `nb::block!`.
+    ///
+    /// Without a BUSY pin ([`NoBusy`](crate::pin::NoBusy)) this is not non-blocking: the first
+    /// poll waits out the worst case time of the refresh, up to 20 s, and returns `Ok`.
+    pub fn poll_refresh(&mut self) -> nb::Result<(), Infallible> {
+        if self.is_busy() {
+            Err(nb::Error::WouldBlock)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Wait until the controller is idle
+    pub(crate) fn wait_until_idle(&mut self) {
+        self.busy.wait_until_idle();
+    }
+
+    /// Put the controller into deep sleep
+    ///
+    /// Only a hardware reset wakes it up again, see [`wake_up`](Self::wake_up).
+    pub fn sleep(&mut self, mode: DeepSleepMode) -> Result<(), DisplayError> {
+        if mode == DeepSleepMode::DiscardRam {
+            self.invalidate_shadow();
+        }
+        self.wait_until_idle();
         self.interface
-            .data_x_times(spi, color, u32::from(WIDTH) / 8 * u32::from(HEIGHT))?;
+            .cmd_with_data(cmd::DEEP_SLEEP_MODE, &[mode.flag()])
+    }
+
+    /// Clear burn-in by cycling the panel through solid frames
+    ///
+    /// Every round shows a full black, a full white and, if enabled, a full red frame, and the
+    /// panel ends on white. Controllers with Auto Write RAM fill the frames themselves, on the
+    /// others the bytes are streamed, either way nothing has to be buffered. This takes several
+    /// full refreshes per round, they count towards the [`RefreshPolicy`] but do not wait for it.
+    pub fn deghost(&mut self, deghost: Deghost) -> Result<(), DisplayError> {
+        let white = color::Color::White.get_byte_value();
+        let black = color::Color::Black.get_byte_value();
+        let no_red = color::Color::White.inverse().get_byte_value();
+        let red = color::Color::Black.inverse().get_byte_value();
+
+        for _ in 0..deghost.cycles {
+            self.show_solid(black, no_red)?;
+            self.show_solid(white, no_red)?;
+            if deghost.red {
+                self.show_solid(white, red)?;
+            }
+        }
+        self.show_solid(white, no_red)?;
+
+        if let Some(mode) = deghost.sleep {
+            self.sleep(mode)?;
+        }
         Ok(())
     }
 
-    /// Make the whole red frame on the display driver white
-    pub fn clear_red_frame(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
-        self.use_full_frame(spi)?;
+    /// Wake the controller up from deep sleep
+    ///
+    /// This resets and initialises the controller, the settings of the driver are restored.
+    ///
+    /// Fails with [`DisplayError::RSError`] without a RST pin ([`NoPin`](crate::pin::NoPin)),
+    /// a controller in deep sleep ignores everything but a hardware reset.
+    pub fn wake_up<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        if !RST::CAN_RESET {
+            return Err(DisplayError::RSError);
+        }
+        self.init(delay)
+    }
 
+    /// Make the whole black and white frame on the display driver white
+    pub fn clear_bw_frame(&mut self) -> Result<(), DisplayError> {
+        // TODO: allow non-white background color
+        let color = color::Color::White.get_byte_value();
+        self.fill_plane(RamPlane::BlackWhite, color)
+    }
+
+    /// Make the whole red frame on the display driver white
+    pub fn clear_red_frame(&mut self) -> Result<(), DisplayError> {
         // TODO: allow non-white background color
         let color = color::Color::White.inverse().get_byte_value();
+        self.fill_plane(RamPlane::Red, color)
+    }
+
+    /// Write a waveform look up table into the LUT register
+    ///
+    /// Panics if `lut` is not [`Controller::LUT_LEN`] bytes long.
+    pub fn write_lut(&mut self, lut: &[u8]) -> Result<(), DisplayError> {
+        assert_eq!(lut.len(), C::LUT_LEN);
+        self.interface.cmd_with_data(cmd::WRITE_LUT, lut)
+    }
+
+    /// Send a command without data and wait until the controller is done with it
+    pub(crate) fn cmd_and_wait(&mut self, command: u8) -> Result<(), DisplayError> {
+        self.interface.cmd(command)?;
+        self.wait_for(BusyWith::Command);
+        Ok(())
+    }
+
+    // Wait for `operation` the controller was just asked to do
+    fn wait_for(&mut self, operation: BusyWith) {
+        self.busy.start(operation);
+        self.busy.wait_until_idle();
+    }
+
+    fn send_driver_control(&mut self) -> Result<(), DisplayError> {
+        let gates = self.size.height - 1;
+        self.interface.cmd_with_data(
+            cmd::DRIVER_CONTROL,
+            &[gates as u8, (gates >> 8) as u8, self.gate_scan.flag()],
+        )
+    }
+
+    // Width of the RAM window, the panel width padded to whole bytes
+    fn ram_width(&self) -> u32 {
+        self.size.row_len() as u32 * 8
+    }
+
+    fn full_area(&self) -> FrameArea {
+        FrameArea {
+            x: 0,
+            y: 0,
+            width: self.ram_width(),
+            height: u32::from(self.size.height),
+        }
+    }
+
+    fn shadow(&mut self, plane: RamPlane) -> &mut RamShadow<SHADOW_ROWS> {
+        match plane {
+            RamPlane::BlackWhite => &mut self.bw_shadow,
+            RamPlane::Red => &mut self.red_shadow,
+        }
+    }
+
+    fn update_plane(&mut self, plane: RamPlane, buffer: &[u8]) -> Result<(), DisplayError> {
+        let size = self.size;
+        let upload = self.shadow(plane).update(size, buffer);
+        let result = self.upload_plane(plane, buffer, upload);
+        if result.is_err() {
+            // the RAM might hold anything now
+            self.shadow(plane).invalidate();
+        }
+        result
+    }
+
+    fn upload_plane(
+        &mut self,
+        plane: RamPlane,
+        buffer: &[u8],
+        upload: Upload,
+    ) -> Result<(), DisplayError> {
+        match upload {
+            Upload::Skip => Ok(()),
+            Upload::All => {
+                self.use_full_frame()?;
+                self.write_ram(plane.write_command(), buffer)
+            }
+            Upload::Rows(first, last) => {
+                let rows = FrameArea {
+                    y: first as u32,
+                    height: (last - first + 1) as u32,
+                    ..self.full_area()
+                };
+                self.use_area(rows)?;
+                let row_len = self.size.row_len();
+                let rows = &buffer[first * row_len..(last + 1) * row_len];
+                self.write_ram(plane.write_command(), rows)
+            }
+        }
+    }
+
+    fn update_plane_area(
+        &mut self,
+        plane: RamPlane,
+        buffer: &[u8],
+        area: FrameArea,
+    ) -> Result<(), DisplayError> {
+        if area == self.full_area() {
+            return self.update_plane(plane, buffer);
+        }
+        self.assert_area(area);
+
+        let (first, last) = (area.y as usize, (area.y + area.height - 1) as usize);
+        let (left, right) = ((area.x / 8) as usize, ((area.x + area.width) / 8) as usize);
+        let result = self.use_area(area).and_then(|()| {
+            self.interface.cmd(plane.write_command())?;
+            for row in buffer
+                .chunks(self.size.row_len())
+                .take(last + 1)
+                .skip(first)
+            {
+                if self.data_entry_mode.increments_x() {
+                    self.interface.data(&row[left..right])?;
+                } else {
+                    self.interface
+                        .data_mapped(&row[left..right], u8::reverse_bits)?;
+                }
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => {
+                let size = self.size;
+                self.shadow(plane).update_rows(size, buffer, first, last)
+            }
+            Err(_) => self.shadow(plane).invalidate(),
+        }
+        result
+    }
+
+    fn activate_refresh(&mut self, kind: RefreshKind) -> Result<(), DisplayError> {
+        let (sequence, operation) = match kind {
+            RefreshKind::Full => (C::UPDATE_FULL, BusyWith::FullRefresh),
+            RefreshKind::Partial => (C::UPDATE_PARTIAL, BusyWith::PartialRefresh),
+        };
+        self.run_sequence(sequence)?;
+        self.busy.start(operation);
+        Ok(())
+    }
 
-        self.interface.cmd(spi, cmd::WRITE_RED_DATA)?;
+    // Start the Display Update Control 2 `sequence`
+    fn run_sequence(&mut self, sequence: u8) -> Result<(), DisplayError> {
         self.interface
-            .data_x_times(spi, color, u32::from(WIDTH) / 8 * u32::from(HEIGHT))?;
+            .cmd_with_data(cmd::UPDATE_DISPLAY_CTRL2, &[sequence])?;
+        self.interface.cmd(cmd::MASTER_ACTIVATE)
+    }
+
+    fn assert_area(&self, area: FrameArea) {
+        assert!(area.x % 8 == 0 && area.width % 8 == 0);
+        assert!(area.width > 0 && area.x + area.width <= self.ram_width());
+        assert!(area.height > 0 && area.y + area.height <= u32::from(self.size.height));
+    }
+
+    fn show_solid(&mut self, bw: u8, red: u8) -> Result<(), DisplayError> {
+        self.fill_plane(RamPlane::BlackWhite, bw)?;
+        self.fill_plane(RamPlane::Red, red)?;
+        self.display_frame()
+    }
+
+    fn fill_plane(&mut self, plane: RamPlane, value: u8) -> Result<(), DisplayError> {
+        self.shadow(plane).invalidate();
+        match C::AUTO_WRITE_STEP {
+            // a single step of the pattern covers the RAM, bit 7 is its color
+            Some(step) if value == 0x00 || value == 0xFF => {
+                self.interface
+                    .cmd_with_data(plane.auto_write_command(), &[value & 0x80 | step])?;
+                self.wait_for(BusyWith::Command);
+            }
+            _ => {
+                self.use_full_frame()?;
+                self.interface.cmd(plane.write_command())?;
+                self.interface
+                    .data_x_times(value, self.size.buffer_len() as u32)?;
+            }
+        }
+        let size = self.size;
+        self.shadow(plane).fill(size, value);
         Ok(())
     }
 
-    fn use_full_frame(&mut self, spi: &mut SPI) -> Result<(), SPI::Error> {
+    fn write_ram(&mut self, command: u8, buffer: &[u8]) -> Result<(), DisplayError> {
+        self.interface.cmd(command)?;
+        if self.data_entry_mode.increments_x() {
+            self.interface.data(buffer)
+        } else {
+            // bits in a byte are always filled left to right, so they have to be mirrored too
+            self.interface.data_mapped(buffer, u8::reverse_bits)
+        }
+    }
+
+    fn use_full_frame(&mut self) -> Result<(), DisplayError> {
         // choose full frame/ram
-        self.set_ram_area(spi, 0, 0, u32::from(WIDTH) - 1, u32::from(HEIGHT) - 1)?;
+        let area = self.full_area();
+        self.use_area(area)
+    }
 
-        // start from the beginning
-        self.set_ram_counter(spi, 0, 0)
+    // Window over the RAM that the buffer `area` is sent to
+    fn use_area(&mut self, area: FrameArea) -> Result<(), DisplayError> {
+        let (right, bottom) = (self.ram_width() - 1, u32::from(self.size.height) - 1);
+        let (end_x, end_y) = (area.x + area.width - 1, area.y + area.height - 1);
+        // a decrementing counter mirrors the buffer in RAM
+        let (start_x, end_x) = if self.data_entry_mode.increments_x() {
+            (area.x, end_x)
+        } else {
+            (right - end_x, right - area.x)
+        };
+        let (start_y, end_y) = if self.data_entry_mode.increments_y() {
+            (area.y, end_y)
+        } else {
+            (bottom - end_y, bottom - area.y)
+        };
+        self.use_window(start_x, start_y, end_x, end_y)
+    }
+
+    fn use_window(
+        &mut self,
+        start_x: u32,
+        start_y: u32,
+        end_x: u32,
+        end_y: u32,
+    ) -> Result<(), DisplayError> {
+        self.set_ram_area(start_x, start_y, end_x, end_y)?;
+
+        // start from the corner the data entry mode starts in
+        let x = if self.data_entry_mode.increments_x() {
+            start_x
+        } else {
+            end_x
+        };
+        let y = if self.data_entry_mode.increments_y() {
+            start_y
+        } else {
+            end_y
+        };
+        self.set_ram_counter(x, y)
     }
 
     fn set_ram_area(
         &mut self,
-        spi: &mut SPI,
         start_x: u32,
         start_y: u32,
         end_x: u32,
         end_y: u32,
-    ) -> Result<(), SPI::Error> {
-        assert!(start_x < end_x);
-        assert!(start_y < end_y);
+    ) -> Result<(), DisplayError> {
+        assert!(start_x <= end_x);
+        assert!(start_y <= end_y);
+
+        // the window is given in the order the address counter moves
+        let (start_x, end_x) = if self.data_entry_mode.increments_x() {
+            (start_x, end_x)
+        } else {
+            (end_x, start_x)
+        };
+        let (start_y, end_y) = if self.data_entry_mode.increments_y() {
+            (start_y, end_y)
+        } else {
+            (end_y, start_y)
+        };
 
         self.interface.cmd_with_data(
-            spi,
             cmd::SET_RAMXPOS,
             &[(start_x >> 3) as u8, (end_x >> 3) as u8],
         )?;
 
         self.interface.cmd_with_data(
-            spi,
             cmd::SET_RAMYPOS,
             &[
                 start_y as u8,
@@ -161,23 +1119,640 @@ where
         Ok(())
     }
 
-    fn set_ram_counter(&mut self, spi: &mut SPI, x: u32, y: u32) -> Result<(), SPI::Error> {
+    fn set_ram_counter(&mut self, x: u32, y: u32) -> Result<(), DisplayError> {
         // x is positioned in bytes, so the last 3 bits which show the position inside a byte in the ram
         // aren't relevant
         self.interface
-            .cmd_with_data(spi, cmd::SET_RAMX_COUNTER, &[(x >> 3) as u8])?;
+            .cmd_with_data(cmd::SET_RAMX_COUNTER, &[(x >> 3) as u8])?;
 
         // 2 Databytes: A[7:0] & 0..A[8]
         self.interface
-            .cmd_with_data(spi, cmd::SET_RAMY_COUNTER, &[y as u8, (y >> 8) as u8])?;
+            .cmd_with_data(cmd::SET_RAMY_COUNTER, &[y as u8, (y >> 8) as u8])?;
+        Ok(())
+    }
+}
+
+impl<DI, BUSY, RST, C, const SHADOW_ROWS: usize> Ssd1681<DI, BUSY, RST, C, SHADOW_ROWS>
+where
+    DI: WriteOnlyDataCommand + ReadData,
+    BUSY: BusyPin,
+    RST: ResetPin,
+    C: Controller,
+{
+    /// Read the whole BW RAM of the display driver into `buffer`
+    pub fn read_bw_ram(&mut self, buffer: &mut [u8]) -> Result<(), ReadError> {
+        self.read_ram(RamPlane::BlackWhite, buffer)
+    }
+
+    /// Read the whole Red RAM of the display driver into `buffer`
+    pub fn read_red_ram(&mut self, buffer: &mut [u8]) -> Result<(), ReadError> {
+        self.read_ram(RamPlane::Red, buffer)
+    }
+
+    /// Read the RAM of `plane` into `buffer`
+    ///
+    /// The data comes back in the same layout it was written in with the current data entry
+    /// mode, so it can be compared with the buffer given to `update_*_frame` directly.
+    ///
+    /// Panics if `buffer` is not as long as a whole plane.
+    pub fn read_ram(&mut self, plane: RamPlane, buffer: &mut [u8]) -> Result<(), ReadError> {
+        let area = self.full_area();
+        self.read_area(plane, area, buffer)
+    }
+
+    /// Read `area` of the RAM of `plane` into `buffer`
+    ///
+    /// The counterpart of [`write_area`](Self::write_area), `buffer` is filled with the rows of
+    /// the area, each `area.width / 8` bytes long.
+    ///
+    /// Panics if `buffer` does not hold exactly the rows of the area.
+    pub fn read_area(
+        &mut self,
+        plane: RamPlane,
+        area: FrameArea,
+        buffer: &mut [u8],
+    ) -> Result<(), ReadError> {
+        self.assert_area(area);
+        assert_eq!(buffer.len(), (area.width / 8 * area.height) as usize);
+
+        let len = (area.width / 8) as usize * self.rows_per_read(area) as usize;
+        self.select_read_plane(plane)?;
+        for (rows, chunk) in self.row_groups(area).zip(buffer.chunks_mut(len)) {
+            self.read_rows(rows, chunk)?;
+        }
         Ok(())
     }
 
-    // pub fn wake_up<DELAY: DelayMs<u8>>(
-    //     &mut self,
-    //     spi: &mut SPI,
-    //     delay: &mut DELAY,
-    // ) -> Result<(), SPI::Error> {
-    //     todo!()
-    // }
+    /// Check that the RAM of `plane` holds `buffer`
+    ///
+    /// The RAM is read back a few rows at a time and its CRC compared with the CRC of
+    /// `buffer`, so no second frame buffer is needed.
+    pub fn verify_frame(
+        &mut self,
+        plane: RamPlane,
+        buffer: &[u8],
+    ) -> Result<(), VerifyError<ReadError>> {
+        let expected = crc16(buffer);
+        let mut actual = crc16(&[]);
+        let area = self.full_area();
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        self.select_read_plane(plane)?;
+        for rows in self.row_groups(area) {
+            let chunk = &mut chunk[..(rows.width / 8 * rows.height) as usize];
+            self.read_rows(rows, chunk)?;
+            actual = crc16_update(actual, chunk);
+        }
+
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(VerifyError::Mismatch { expected, actual })
+        }
+    }
+
+    fn select_read_plane(&mut self, plane: RamPlane) -> Result<(), DisplayError> {
+        self.interface
+            .cmd_with_data(cmd::READ_RAM_OPTION, &[plane.read_option()])
+    }
+
+    // Rows of `area` that fit into one read
+    fn rows_per_read(&self, area: FrameArea) -> u32 {
+        (READ_CHUNK_SIZE as u32 / (area.width / 8)).max(1)
+    }
+
+    // `area` split into the rows read at a time
+    fn row_groups(&self, area: FrameArea) -> impl Iterator<Item = FrameArea> {
+        let rows = self.rows_per_read(area);
+        (area.y..area.y + area.height)
+            .step_by(rows as usize)
+            .map(move |y| FrameArea {
+                height: rows.min(area.y + area.height - y),
+                y,
+                ..area
+            })
+    }
+
+    // Read `area` into `buffer` with a single read transaction
+    fn read_rows(&mut self, area: FrameArea, buffer: &mut [u8]) -> Result<(), ReadError> {
+        self.use_area(area)?;
+        self.interface.cmd_read_ram(cmd::READ_RAM, buffer)?;
+        if !self.data_entry_mode.increments_x() {
+            for byte in buffer.iter_mut() {
+                *byte = byte.reverse_bits();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{vec, vec::Vec};
+
+    use display_interface_spi::SPIInterface;
+
+    use super::{DataEntryMode, DeepSleepMode, Deghost, FrameArea, GateScan, Ssd1681, PANEL_1IN54};
+    use crate::cmd;
+    use crate::controller::{PanelSize, Ssd1608, Ssd1680};
+    use crate::interface::{DisplayError, SpiInterface, ThreeWireSpiInterface};
+    use crate::mock::{Delays, Idle, NoDelay, Pin, Sim, Ticks};
+    use crate::otp::{DisplayOption, OtpUnlock, UserId};
+    use crate::pin::{NoBusy, NoPin};
+    use crate::ram::{RamPlane, RamShadow, VerifyError};
+    use crate::refresh::{RefreshError, RefreshKind, RefreshPolicy, TooSoon};
+    use crate::status::Fault;
+
+    #[test]
+    fn default_entry_mode_writes_frame_as_is() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+
+        ssd1681.update_bw_frame(&[0x0F, 0x01]).unwrap();
+
+        assert_eq!(sim.data_for(cmd::DATA_ENTRY_MODE).unwrap(), [0b11]);
+        assert_eq!(sim.data_for(cmd::SET_RAMXPOS).unwrap(), [0, 24]);
+        assert_eq!(sim.data_for(cmd::SET_RAMYPOS).unwrap(), [0, 0, 199, 0]);
+        assert_eq!(sim.data_for(cmd::SET_RAMX_COUNTER).unwrap(), [0]);
+        assert_eq!(sim.data_for(cmd::SET_RAMY_COUNTER).unwrap(), [0, 0]);
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap(), [0x0F, 0x01]);
+    }
+
+    #[test]
+    fn decrementing_entry_mode_reverses_window_and_bits() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+
+        ssd1681
+            .set_data_entry_mode(DataEntryMode::DecrYDecrX)
+            .unwrap();
+        ssd1681.update_red_frame(&[0x0F, 0x01]).unwrap();
+
+        assert_eq!(sim.data_for(cmd::DATA_ENTRY_MODE).unwrap(), [0b00]);
+        assert_eq!(sim.data_for(cmd::SET_RAMXPOS).unwrap(), [24, 0]);
+        assert_eq!(sim.data_for(cmd::SET_RAMYPOS).unwrap(), [199, 0, 0, 0]);
+        assert_eq!(sim.data_for(cmd::SET_RAMX_COUNTER).unwrap(), [24]);
+        assert_eq!(sim.data_for(cmd::SET_RAMY_COUNTER).unwrap(), [199, 0]);
+        assert_eq!(sim.data_for(cmd::WRITE_RED_DATA).unwrap(), [0xF0, 0x80]);
+    }
+
+    #[test]
+    fn entry_mode_survives_init() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+
+        ssd1681
+            .set_data_entry_mode(DataEntryMode::IncrYDecrX)
+            .unwrap();
+        sim.clear();
+        ssd1681.init(&mut NoDelay).unwrap();
+
+        assert_eq!(sim.data_for(cmd::DATA_ENTRY_MODE).unwrap(), [0b10]);
+    }
+
+    #[test]
+    fn gate_scan_bits_in_driver_control() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        assert_eq!(sim.data_for(cmd::DRIVER_CONTROL).unwrap(), [199, 0, 0]);
+
+        let gate_scan = GateScan {
+            bottom_to_top: true,
+            interlaced: true,
+            ..GateScan::default()
+        };
+        ssd1681.set_gate_scan(gate_scan).unwrap();
+        assert_eq!(sim.data_for(cmd::DRIVER_CONTROL).unwrap(), [199, 0, 0b011]);
+
+        sim.clear();
+        ssd1681.init(&mut NoDelay).unwrap();
+        assert_eq!(sim.data_for(cmd::DRIVER_CONTROL).unwrap(), [199, 0, 0b011]);
+    }
+
+    #[test]
+    fn self_test_reports_faults() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+
+        sim.respond(cmd::STATUS_BIT_READ, &[0b0000_0001]);
+        let diagnostic = ssd1681.self_test().unwrap();
+        assert!(diagnostic.is_ok());
+        assert_eq!(sim.data_for(cmd::VCI_DETECTION).unwrap(), [0b101]);
+        assert_eq!(sim.data_for(cmd::UPDATE_DISPLAY_CTRL2).unwrap(), [0x03]);
+
+        sim.respond(cmd::STATUS_BIT_READ, &[0b0011_0001]);
+        let diagnostic = ssd1681.self_test().unwrap();
+        assert_eq!(diagnostic.fault(), Some(Fault::LowVoltage));
+
+        // a floating bus reads all ones
+        sim.clear();
+        sim.respond(cmd::STATUS_BIT_READ, &[0xFF]);
+        let diagnostic = ssd1681.self_test().unwrap();
+        assert_eq!(diagnostic.fault(), Some(Fault::NotResponding));
+        assert_eq!(sim.data_for(cmd::VCI_DETECTION), None);
+    }
+
+    #[test]
+    fn read_back_and_verify_ram() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+
+        // five rows of 25 bytes are read at a time, the first byte read is a dummy
+        let rows: Vec<u8> = (0..125).collect();
+        let mut response = vec![0x00];
+        response.extend_from_slice(&rows);
+        sim.respond(cmd::READ_RAM, &response);
+        let frame = rows.repeat(40);
+
+        let mut buffer = [0; 5000];
+        ssd1681.read_red_ram(&mut buffer).unwrap();
+        assert_eq!(sim.data_for(cmd::READ_RAM_OPTION).unwrap(), [0x01]);
+        assert_eq!(&buffer[..], &frame[..]);
+
+        ssd1681.verify_frame(RamPlane::BlackWhite, &frame).unwrap();
+        assert_eq!(sim.data_for(cmd::READ_RAM_OPTION).unwrap(), [0x00]);
+
+        let mut changed = frame.clone();
+        changed[300] ^= 0x10;
+        let result = ssd1681.verify_frame(RamPlane::BlackWhite, &changed);
+        assert!(matches!(result, Err(VerifyError::Mismatch { .. })));
+    }
+
+    #[test]
+    #[should_panic]
+    fn read_area_checks_buffer_len() {
+        let sim = Sim::new();
+        let area = FrameArea {
+            x: 0,
+            y: 0,
+            width: 16,
+            height: 2,
+        };
+        let _ = sim.driver().read_area(RamPlane::Red, area, &mut [0; 3]);
+    }
+
+    #[test]
+    fn ram_reads_keep_chip_select() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        sim.respond(cmd::READ_RAM, &[0x00, 0x12, 0x34, 0x56]);
+        sim.clear();
+
+        // the dummy byte and the data are read in a single transaction
+        let mut buffer = [0; 3];
+        let area = FrameArea {
+            x: 16,
+            y: 8,
+            width: 24,
+            height: 1,
+        };
+        ssd1681
+            .read_area(RamPlane::BlackWhite, area, &mut buffer)
+            .unwrap();
+        assert_eq!(buffer, [0x12, 0x34, 0x56]);
+        assert_eq!(sim.read_transactions(), 1);
+
+        // every read of a whole plane is one transaction too
+        sim.clear();
+        ssd1681
+            .verify_frame(RamPlane::BlackWhite, &[0xFF; 5000])
+            .unwrap_err();
+        let reads = sim
+            .commands()
+            .iter()
+            .filter(|(c, _)| *c == cmd::READ_RAM)
+            .count();
+        assert_eq!(reads, 40);
+        assert_eq!(sim.read_transactions(), reads);
+    }
+
+    #[test]
+    fn otp_display_option_and_user_id() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+
+        sim.respond(
+            cmd::READ_DISPLAY_OPTION,
+            &[0x00, 0x50, 0x03, 0, 0, 0, 0, 0, 0, 0, 0x02],
+        );
+        sim.respond(cmd::READ_USER_ID, b"panel-0042");
+
+        let option = ssd1681.read_display_option().unwrap();
+        assert_eq!(option.vcom, 0x50);
+        assert_eq!(option.display_mode_2, 0b11);
+        assert_eq!(option.waveform_version, 2);
+        assert_eq!(ssd1681.read_user_id().unwrap().as_bytes(), b"panel-0042");
+
+        let unlock = OtpUnlock::i_understand_otp_is_irreversible();
+        let option = DisplayOption {
+            waveform_version: 3,
+            ..option
+        };
+        ssd1681
+            .write_register_for_display_option(&option, &unlock)
+            .unwrap();
+        ssd1681
+            .write_user_id(&UserId(*b"panel-0043"), &unlock)
+            .unwrap();
+
+        assert_eq!(
+            sim.data_for(cmd::WRITE_DISPLAY_OPTION).unwrap(),
+            [0x00, 0x03, 0, 0, 0, 0, 0, 0, 0, 0x03]
+        );
+        assert_eq!(sim.data_for(cmd::WRITE_USER_ID).unwrap(), b"panel-0043");
+    }
+
+    #[test]
+    fn non_blocking_refresh() {
+        let sim = Sim::new();
+        let mut ssd1681 = Ssd1681::new(sim.spi(), sim.busy(), sim.dc(), Pin, &mut NoDelay).unwrap();
+
+        ssd1681.start_refresh().unwrap();
+        assert_eq!(sim.data_for(cmd::UPDATE_DISPLAY_CTRL2).unwrap(), [0xF7]);
+        assert!(sim.data_for(cmd::MASTER_ACTIVATE).is_some());
+
+        sim.set_busy(true);
+        assert!(ssd1681.is_busy());
+        assert_eq!(ssd1681.poll_refresh(), Err(nb::Error::WouldBlock));
+
+        sim.set_busy(false);
+        assert!(!ssd1681.is_busy());
+        assert_eq!(ssd1681.poll_refresh(), Ok(()));
+    }
+
+    #[test]
+    fn shadow_skips_and_shrinks_uploads() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver().with_shadow::<200>();
+        let mut frame = [0xFF; 5000];
+
+        ssd1681.update_bw_frame(&frame).unwrap();
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap().len(), 5000);
+
+        sim.clear();
+        ssd1681.update_bw_frame(&frame).unwrap();
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA), None);
+
+        frame[10 * 25] = 0x00;
+        frame[12 * 25 + 24] = 0x00;
+        ssd1681.update_bw_frame(&frame).unwrap();
+        assert_eq!(sim.data_for(cmd::SET_RAMYPOS).unwrap(), [10, 0, 12, 0]);
+        assert_eq!(sim.data_for(cmd::SET_RAMY_COUNTER).unwrap(), [10, 0]);
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap(), &frame[250..325]);
+
+        // the red plane has its own shadow
+        sim.clear();
+        ssd1681.update_red_frame(&frame).unwrap();
+        assert_eq!(sim.data_for(cmd::WRITE_RED_DATA).unwrap().len(), 5000);
+
+        sim.clear();
+        ssd1681.sleep(DeepSleepMode::DiscardRam).unwrap();
+        ssd1681.update_bw_frame(&frame).unwrap();
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap().len(), 5000);
+    }
+
+    #[test]
+    fn shadow_is_opt_in() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        let frame = [0xFF; 5000];
+
+        ssd1681.update_bw_frame(&frame).unwrap();
+        sim.clear();
+        ssd1681.update_bw_frame(&frame).unwrap();
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap().len(), 5000);
+
+        // and keeps no rows when off
+        assert!(core::mem::size_of::<RamShadow<0>>() <= 2);
+    }
+
+    #[test]
+    fn shadow_rows_follow_entry_mode() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver().with_shadow::<200>();
+        ssd1681
+            .set_data_entry_mode(DataEntryMode::DecrYIncrX)
+            .unwrap();
+        ssd1681.clear_bw_frame().unwrap();
+
+        let mut frame = [0xFF; 5000];
+        frame[0] = 0x00;
+        sim.clear();
+        ssd1681.update_bw_frame(&frame).unwrap();
+
+        assert_eq!(sim.data_for(cmd::SET_RAMYPOS).unwrap(), [199, 0, 199, 0]);
+        assert_eq!(sim.data_for(cmd::SET_RAMY_COUNTER).unwrap(), [199, 0]);
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap(), &frame[..25]);
+    }
+
+    #[test]
+    fn update_area_sends_window() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        ssd1681
+            .set_data_entry_mode(DataEntryMode::IncrYDecrX)
+            .unwrap();
+        let mut frame = [0xFF; 5000];
+        frame[3 * 25 + 1] = 0x01;
+        frame[4 * 25 + 2] = 0x80;
+
+        let area = FrameArea {
+            x: 8,
+            y: 3,
+            width: 16,
+            height: 2,
+        };
+        ssd1681.update_bw_area(&frame, area).unwrap();
+        // bytes 1 and 2 of the buffer are bytes 23 and 22 of the mirrored RAM
+        assert_eq!(sim.data_for(cmd::SET_RAMXPOS).unwrap(), [23, 22]);
+        assert_eq!(sim.data_for(cmd::SET_RAMYPOS).unwrap(), [3, 0, 4, 0]);
+        assert_eq!(sim.data_for(cmd::SET_RAMX_COUNTER).unwrap(), [23]);
+        assert_eq!(
+            sim.data_for(cmd::WRITE_BW_DATA).unwrap(),
+            [0x80, 0xFF, 0xFF, 0x01]
+        );
+    }
+
+    #[test]
+    fn refresh_follows_policy() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        let mut clock = Ticks::default();
+        let mut delay = clock.clone();
+        ssd1681.set_refresh_policy(RefreshPolicy {
+            full_every: Some(1),
+            min_interval_ms: 1_000,
+            ..RefreshPolicy::default()
+        });
+
+        let kind = ssd1681
+            .refresh(&mut clock, &mut delay, RefreshKind::Partial)
+            .unwrap();
+        assert_eq!(kind, RefreshKind::Partial);
+        assert_eq!(sim.data_for(cmd::UPDATE_DISPLAY_CTRL2).unwrap(), [0xFF]);
+
+        match ssd1681.refresh(&mut clock, &mut delay, RefreshKind::Partial) {
+            Err(RefreshError::TooSoon { remaining_ms }) => assert_eq!(remaining_ms, 900),
+            _ => panic!("refresh was not rejected"),
+        }
+
+        ssd1681.set_refresh_policy(RefreshPolicy {
+            too_soon: TooSoon::Defer,
+            ..ssd1681.refresh_policy()
+        });
+        let kind = ssd1681
+            .refresh(&mut clock, &mut delay, RefreshKind::Partial)
+            .unwrap();
+        assert_eq!(kind, RefreshKind::Full);
+        // read at 300, slept 800 and read again
+        assert_eq!(clock.get(), 1_200);
+        assert_eq!(sim.data_for(cmd::UPDATE_DISPLAY_CTRL2).unwrap(), [0xF7]);
+
+        // refreshes without a clock count as done at the next read, 1300
+        ssd1681.display_partial_frame().unwrap();
+        ssd1681
+            .refresh(&mut clock, &mut delay, RefreshKind::Full)
+            .unwrap();
+        assert_eq!(clock.get(), 2_400);
+    }
+
+    #[test]
+    fn deghost_cycles_solid_frames() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        sim.clear();
+
+        let deghost = Deghost {
+            cycles: 2,
+            sleep: Some(DeepSleepMode::RetainRam),
+            ..Deghost::default()
+        };
+        ssd1681.deghost(deghost).unwrap();
+
+        let mut frames = Vec::new();
+        let mut bw = 0;
+        for (command, data) in sim.commands() {
+            match command {
+                cmd::AUTO_WRITE_BW => bw = data[0],
+                cmd::AUTO_WRITE_RED => frames.push((bw, data[0])),
+                _ => (),
+            }
+        }
+        // 200x200 steps of the first color
+        let round = [(0x55, 0x55), (0xD5, 0x55), (0xD5, 0xD5)];
+        assert_eq!(frames[..3], round);
+        assert_eq!(frames[3..6], round);
+        assert_eq!(frames[6..], [(0xD5, 0x55)]);
+
+        let refreshes = sim
+            .commands()
+            .iter()
+            .filter(|(command, _)| *command == cmd::MASTER_ACTIVATE)
+            .count();
+        assert_eq!(refreshes, 7);
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA), None);
+        assert_eq!(sim.commands().last().unwrap().0, cmd::DEEP_SLEEP_MODE);
+    }
+
+    #[test]
+    fn other_controllers_use_panel_size() {
+        let sim = Sim::new();
+        let size = PanelSize {
+            width: 122,
+            height: 250,
+        };
+        // any display-interface transport will do
+        let interface = SPIInterface::new(sim.spi(), sim.dc());
+        let mut epd: Ssd1681<_, _, _, Ssd1680> =
+            Ssd1681::with_interface(interface, Idle, Pin, size, &mut NoDelay).unwrap();
+        assert_eq!(sim.data_for(cmd::DRIVER_CONTROL).unwrap(), [249, 0, 0]);
+        assert_eq!(sim.data_for(cmd::SET_RAMXPOS).unwrap(), [0, 15]);
+        assert_eq!(sim.data_for(cmd::SET_RAMYPOS).unwrap(), [0, 0, 249, 0]);
+
+        epd.clear_bw_frame().unwrap();
+        assert_eq!(sim.data_for(cmd::AUTO_WRITE_BW).unwrap(), [0xE5]);
+
+        let sim = Sim::new();
+        let size = PanelSize {
+            width: 128,
+            height: 296,
+        };
+        let interface = SpiInterface::new(sim.spi(), sim.dc());
+        let mut epd: Ssd1681<_, _, _, Ssd1608> =
+            Ssd1681::with_interface(interface, Idle, Pin, size, &mut NoDelay).unwrap();
+        assert_eq!(sim.data_for(cmd::DRIVER_CONTROL).unwrap(), [0x27, 0x01, 0]);
+        assert_eq!(sim.data_for(cmd::BORDER_WAVEFORM_CONTROL).unwrap(), [0x33]);
+        assert_eq!(sim.data_for(cmd::TEMP_CONTROL), None);
+
+        // no Auto Write RAM
+        epd.clear_bw_frame().unwrap();
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap().len(), 16 * 296);
+
+        // the LUT written by the host stays
+        epd.display_frame().unwrap();
+        epd.display_partial_frame().unwrap();
+        let sequences: Vec<_> = sim
+            .commands()
+            .into_iter()
+            .filter(|(command, _)| *command == cmd::UPDATE_DISPLAY_CTRL2)
+            .map(|(_, data)| data)
+            .collect();
+        assert_eq!(sequences, [[0xC7], [0xC7]]);
+    }
+
+    #[test]
+    fn three_wire_transport() {
+        let sim = Sim::three_wire();
+        let interface = ThreeWireSpiInterface::new(sim.spi());
+        let mut ssd1681: Ssd1681<_, _, _> =
+            Ssd1681::with_interface(interface, Idle, Pin, PANEL_1IN54, &mut NoDelay).unwrap();
+        assert_eq!(sim.data_for(cmd::DRIVER_CONTROL).unwrap(), [199, 0, 0]);
+
+        let frame = [0x5A; 5000];
+        ssd1681.update_bw_frame(&frame).unwrap();
+        ssd1681.display_frame().unwrap();
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap(), frame);
+        assert_eq!(sim.data_for(cmd::UPDATE_DISPLAY_CTRL2).unwrap(), [0xF7]);
+
+        sim.respond(cmd::STATUS_BIT_READ, &[0b0000_0001]);
+        assert!(ssd1681.self_test().unwrap().is_ok());
+    }
+
+    #[test]
+    fn missing_pins_fall_back_to_delays() {
+        let sim = Sim::new();
+        let delays = Delays::default();
+        let interface = SpiInterface::new(sim.spi(), sim.dc());
+        let busy = NoBusy::new(delays.clone());
+        let mut ssd1681: Ssd1681<_, _, _> =
+            Ssd1681::with_interface(interface, busy, NoPin, PANEL_1IN54, &mut NoDelay).unwrap();
+        assert!(sim.data_for(cmd::SW_RESET).is_some());
+        assert_eq!(delays.waits(), [20]);
+
+        ssd1681.display_frame().unwrap();
+        ssd1681.display_partial_frame().unwrap();
+        ssd1681.start_refresh().unwrap();
+        assert_eq!(ssd1681.poll_refresh(), Ok(()));
+        assert_eq!(delays.waits(), [20, 20_000, 2_000, 20_000]);
+
+        // nothing but the RST pin wakes the controller up
+        ssd1681.sleep(DeepSleepMode::RetainRam).unwrap();
+        sim.clear();
+        let result = ssd1681.wake_up(&mut NoDelay);
+        assert!(matches!(result, Err(DisplayError::RSError)));
+        assert!(sim.commands().is_empty());
+    }
+
+    #[test]
+    fn release_returns_resources() {
+        let sim = Sim::new();
+        let ssd1681 = sim.driver();
+
+        let (interface, busy, rst) = ssd1681.release_sleeping(DeepSleepMode::DiscardRam).unwrap();
+        assert_eq!(sim.data_for(cmd::DEEP_SLEEP_MODE).unwrap(), [0b11]);
+        let (spi, dc) = interface.release();
+        let _ssd1681 = Ssd1681::new(spi, busy, dc, rst, &mut NoDelay).unwrap();
+        assert_eq!(sim.commands().last().unwrap().0, cmd::TEMP_CONTROL);
+    }
 }
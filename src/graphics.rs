@@ -1,13 +1,14 @@
 //! Graphics Support for EPDs
 
-use core::convert::TryFrom;
 use crate::color::Color;
+use crate::driver::{DataEntryMode, FrameArea};
 use crate::{HEIGHT, WIDTH};
-use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+use embedded_graphics::image::GetPixel;
 use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
 
 /// DisplayRotation
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum DisplayRotation {
     /// No rotation
     #[default]
@@ -20,12 +21,56 @@ pub enum DisplayRotation {
     Rotate270,
 }
 
+impl DisplayRotation {
+    /// Data entry mode that lets the controller apply this rotation to an unrotated buffer
+    ///
+    /// Returns `None` for 90 and 270 degrees since the controller RAM packs 8 horizontal
+    /// pixels per byte and those rotations would have to split bytes into columns.
+    pub fn data_entry_mode(self) -> Option<DataEntryMode> {
+        match self {
+            DisplayRotation::Rotate0 => Some(DataEntryMode::IncrYIncrX),
+            DisplayRotation::Rotate180 => Some(DataEntryMode::DecrYDecrX),
+            DisplayRotation::Rotate90 | DisplayRotation::Rotate270 => None,
+        }
+    }
+}
+
+/// Mirroring of the panel, applied after the rotation
+///
+/// Useful for panels mounted upside down or viewed through a mirror.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DisplayMirror {
+    /// No mirroring
+    #[default]
+    None,
+    /// Mirror left and right
+    Horizontal,
+    /// Mirror top and bottom
+    Vertical,
+    /// Mirror both left and right and top and bottom
+    Both,
+}
+
+impl DisplayMirror {
+    /// Data entry mode that lets the controller apply this mirroring to an unmirrored buffer
+    pub fn data_entry_mode(self) -> DataEntryMode {
+        match self {
+            DisplayMirror::None => DataEntryMode::IncrYIncrX,
+            DisplayMirror::Horizontal => DataEntryMode::IncrYDecrX,
+            DisplayMirror::Vertical => DataEntryMode::DecrYIncrX,
+            DisplayMirror::Both => DataEntryMode::DecrYDecrX,
+        }
+    }
+}
+
 /// Necessary traits for all displays to implement for drawing
 ///
 /// Adds support for:
 /// - Drawing (With the help of DrawTarget/Embedded Graphics)
 /// - Rotations
+/// - Mirroring
 /// - Clearing
+/// - Tracking the changed area
 pub trait Display: DrawTarget {
     /// Clears the buffer of the display with the chosen background color
     fn clear_buffer(&mut self, background_color: Color) {
@@ -38,6 +83,8 @@ pub trait Display: DrawTarget {
         for elem in self.get_mut_buffer().iter_mut() {
             *elem = fill_color
         }
+
+        self.mark_dirty(whole_buffer(self));
     }
 
     /// Returns the buffer
@@ -52,9 +99,44 @@ pub trait Display: DrawTarget {
     /// Get the current rotation of the display
     fn rotation(&self) -> DisplayRotation;
 
+    /// Sets the mirroring of the display, returns `false` if `mirror` is not supported
+    ///
+    /// The default implementation does not support mirroring and only accepts
+    /// [`DisplayMirror::None`].
+    fn set_mirror(&mut self, mirror: DisplayMirror) -> bool {
+        mirror == DisplayMirror::None
+    }
+
+    /// Get the current mirroring of the display
+    fn mirror(&self) -> DisplayMirror {
+        DisplayMirror::None
+    }
+
     /// If the color for this display is inverted
     fn is_inverted(&self) -> bool;
 
+    /// Area of the buffer changed since the last [`take_dirty`](Self::take_dirty)
+    ///
+    /// The area is in buffer coordinates, with rotation and mirroring applied, and widened to
+    /// whole bytes so it can be passed to
+    /// [`update_bw_area`](crate::driver::Ssd1681::update_bw_area) as a [`FrameArea`].
+    ///
+    /// The default implementation does not track changes and always returns the whole buffer.
+    fn dirty_area(&self) -> Option<Rectangle> {
+        Some(whole_buffer(self))
+    }
+
+    /// Returns the changed area and starts tracking from scratch
+    fn take_dirty(&mut self) -> Option<Rectangle> {
+        self.dirty_area()
+    }
+
+    /// Adds `area`, in buffer coordinates, to the changed area
+    ///
+    /// Drawing does this by itself, it is only needed after writing to
+    /// [`get_mut_buffer`](Self::get_mut_buffer).
+    fn mark_dirty(&mut self, _area: Rectangle) {}
+
     /// Helperfunction for the Embedded Graphics draw trait
     ///
     /// Becomes uneccesary when `const_generics` become stablised
@@ -65,6 +147,7 @@ pub trait Display: DrawTarget {
         pixel: Pixel<BinaryColor>,
     ) -> Result<(), Self::Error> {
         let rotation = self.rotation();
+        let mirror = self.mirror();
         let is_inverted = self.is_inverted();
         let buffer = self.get_mut_buffer();
 
@@ -74,8 +157,20 @@ pub trait Display: DrawTarget {
         }
 
         // Give us index inside the buffer and the bit-position in that u8 which needs to be changed
-        let (index, bit) = find_position(point.x as u32, point.y as u32, width, height, rotation);
+        let (index, bit) = find_position(
+            point.x as u32,
+            point.y as u32,
+            width,
+            height,
+            rotation,
+            mirror,
+        );
         let index = index as usize;
+        let stride = width.div_ceil(8) as usize;
+        let byte = Rectangle::new(
+            Point::new((index % stride * 8) as i32, (index / stride) as i32),
+            Size::new(8, 1),
+        );
 
         // "Draw" the Pixel on that bit
         match color {
@@ -96,6 +191,7 @@ pub trait Display: DrawTarget {
                 }
             }
         }
+        self.mark_dirty(byte);
         Ok(())
     }
 }
@@ -104,7 +200,9 @@ pub trait Display: DrawTarget {
 pub struct Display1in54 {
     buffer: [u8; WIDTH as usize * HEIGHT as usize / 8],
     rotation: DisplayRotation,
+    mirror: DisplayMirror,
     is_inverted: bool,
+    dirty: Option<Rectangle>,
 }
 
 impl Display1in54 {
@@ -113,7 +211,10 @@ impl Display1in54 {
         Display1in54 {
             buffer: [Color::White.get_byte_value(); buffer_len(WIDTH as usize, HEIGHT as usize)],
             rotation: DisplayRotation::default(),
+            mirror: DisplayMirror::default(),
             is_inverted: true,
+            // not sent to the controller yet
+            dirty: Some(full_area()),
         }
     }
 
@@ -123,8 +224,75 @@ impl Display1in54 {
             buffer: [Color::White.inverse().get_byte_value();
                 buffer_len(WIDTH as usize, HEIGHT as usize)],
             rotation: DisplayRotation::default(),
+            mirror: DisplayMirror::default(),
             is_inverted: false,
+            dirty: Some(full_area()),
+        }
+    }
+
+    /// Points that are black (or red), with the rotation and mirroring applied
+    pub fn set_pixels(&self) -> impl Iterator<Item = Point> + '_ {
+        drawing_area(WIDTH.into(), HEIGHT.into(), self.rotation)
+            .points()
+            .filter(move |&p| self.pixel(p) == Some(BinaryColor::On))
+    }
+
+    /// Rows of pixels from top to bottom, with the rotation and mirroring applied
+    pub fn rows(&self) -> impl Iterator<Item = Row<'_>> + '_ {
+        let area = drawing_area(WIDTH.into(), HEIGHT.into(), self.rotation);
+        area.rows().map(move |y| Row {
+            display: self,
+            y,
+            columns: area.columns(),
+        })
+    }
+}
+
+/// Pixels of one row of a [`Display1in54`] from left to right
+pub struct Row<'a> {
+    display: &'a Display1in54,
+    y: i32,
+    columns: core::ops::Range<i32>,
+}
+
+impl Row<'_> {
+    /// Position of the row from the top
+    pub fn y(&self) -> i32 {
+        self.y
+    }
+}
+
+impl Iterator for Row<'_> {
+    type Item = BinaryColor;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.columns.next()?;
+        self.display.pixel(Point::new(x, self.y))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.columns.size_hint()
+    }
+}
+
+impl GetPixel for Display1in54 {
+    type Color = BinaryColor;
+
+    fn pixel(&self, p: Point) -> Option<Self::Color> {
+        let (width, height) = (WIDTH.into(), HEIGHT.into());
+        if outside_display(p, width, height, self.rotation) {
+            return None;
         }
+        let (index, bit) = find_position(
+            p.x as u32,
+            p.y as u32,
+            width,
+            height,
+            self.rotation,
+            self.mirror,
+        );
+        let set = self.buffer[index as usize] & bit != 0;
+        Some(BinaryColor::from(set != self.is_inverted))
     }
 }
 
@@ -140,11 +308,123 @@ impl DrawTarget for Display1in54 {
 
     fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
     where
-        I: IntoIterator<Item=Pixel<Self::Color>>
+        I: IntoIterator<Item = Pixel<Self::Color>>,
     {
-        <()>::try_from(for p in pixels.into_iter() {
+        for p in pixels.into_iter() {
             self.draw_helper(WIDTH.into(), HEIGHT.into(), p)?;
-        }).expect("TODO: panic message");
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let (width, height) = (WIDTH.into(), HEIGHT.into());
+        let visible = area.intersection(&drawing_area(width, height, self.rotation));
+        if visible.is_zero_sized() {
+            return Ok(());
+        }
+
+        let stride = width.div_ceil(8) as usize;
+        let step = pixel_step(width, height, self.rotation, self.mirror);
+        let skip_left = (visible.top_left.x - area.top_left.x) as usize;
+        let skip_top = (visible.top_left.y - area.top_left.y) as usize;
+        let skip_right = (area.size.width - visible.size.width) as usize - skip_left;
+
+        let mut colors = colors.into_iter();
+        if skip_top > 0 {
+            colors.nth(skip_top * area.size.width as usize - 1);
+        }
+        for y in visible.rows() {
+            if skip_left > 0 {
+                colors.nth(skip_left - 1);
+            }
+            // only the first pixel of a row is looked up, the others are a step away
+            let (index, bit) = find_position(
+                visible.top_left.x as u32,
+                y as u32,
+                width,
+                height,
+                self.rotation,
+                self.mirror,
+            );
+            let (mut index, mut bit) = (index as usize, bit);
+            for color in colors.by_ref().take(visible.size.width as usize) {
+                if color.is_on() != self.is_inverted {
+                    self.buffer[index] |= bit;
+                } else {
+                    self.buffer[index] &= !bit;
+                }
+                // steps past the last pixel are never used, so they may wrap
+                match step {
+                    (1, _) => {
+                        bit = bit.rotate_right(1);
+                        if bit == 0x80 {
+                            index = index.wrapping_add(1);
+                        }
+                    }
+                    (-1, _) => {
+                        bit = bit.rotate_left(1);
+                        if bit == 0x01 {
+                            index = index.wrapping_sub(1);
+                        }
+                    }
+                    (_, dy) => {
+                        index = index.wrapping_add((dy as isize * stride as isize) as usize);
+                    }
+                }
+            }
+            if skip_right > 0 {
+                colors.nth(skip_right - 1);
+            }
+        }
+
+        let area = buffer_area(visible, width, height, self.rotation, self.mirror);
+        self.mark_dirty(area);
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let (width, height) = (WIDTH.into(), HEIGHT.into());
+        let visible = area.intersection(&drawing_area(width, height, self.rotation));
+        if visible.is_zero_sized() {
+            return Ok(());
+        }
+
+        // a rectangle stays one in the buffer, whatever the rotation and mirroring
+        let area = buffer_area(visible, width, height, self.rotation, self.mirror);
+        let stride = width.div_ceil(8) as usize;
+        let set = color.is_on() != self.is_inverted;
+        let left = area.top_left.x as u32;
+        let right = left + area.size.width;
+        let (first, last) = ((left / 8) as usize, ((right - 1) / 8) as usize);
+        for y in area.rows() {
+            let row = &mut self.buffer[y as usize * stride..][..stride];
+            for (i, byte) in row.iter_mut().enumerate().take(last + 1).skip(first) {
+                let from = if i == first { left % 8 } else { 0 };
+                let to = if i == last {
+                    right - 8 * last as u32
+                } else {
+                    8
+                };
+                let mask = byte_mask(from, to);
+                if set {
+                    *byte |= mask;
+                } else {
+                    *byte &= !mask;
+                }
+            }
+        }
+
+        self.mark_dirty(area);
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let set = color.is_on() != self.is_inverted;
+        self.buffer.fill(if set { 0xFF } else { 0x00 });
+        self.mark_dirty(full_area());
         Ok(())
     }
 }
@@ -166,13 +446,146 @@ impl Display for Display1in54 {
         self.rotation
     }
 
+    fn set_mirror(&mut self, mirror: DisplayMirror) -> bool {
+        self.mirror = mirror;
+        true
+    }
+
+    fn mirror(&self) -> DisplayMirror {
+        self.mirror
+    }
+
     fn is_inverted(&self) -> bool {
         self.is_inverted
     }
+
+    fn dirty_area(&self) -> Option<Rectangle> {
+        self.dirty
+    }
+
+    fn take_dirty(&mut self) -> Option<Rectangle> {
+        self.dirty.take()
+    }
+
+    fn mark_dirty(&mut self, area: Rectangle) {
+        let area = align_to_bytes(area).intersection(&full_area());
+        if area.is_zero_sized() {
+            return;
+        }
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => union(dirty, area),
+            None => area,
+        });
+    }
+}
+
+impl From<Rectangle> for FrameArea {
+    fn from(area: Rectangle) -> Self {
+        FrameArea {
+            x: area.top_left.x as u32,
+            y: area.top_left.y as u32,
+            width: area.size.width,
+            height: area.size.height,
+        }
+    }
+}
+
+// The whole buffer of `display` in buffer coordinates
+fn whole_buffer<D: Display + ?Sized>(display: &D) -> Rectangle {
+    let size = display.bounding_box().size;
+    let size = match display.rotation() {
+        DisplayRotation::Rotate0 | DisplayRotation::Rotate180 => size,
+        DisplayRotation::Rotate90 | DisplayRotation::Rotate270 => {
+            Size::new(size.height, size.width)
+        }
+    };
+    Rectangle::new(Point::zero(), size)
+}
+
+fn full_area() -> Rectangle {
+    Rectangle::new(Point::zero(), Size::new(WIDTH as u32, HEIGHT as u32))
+}
+
+// Area that can be drawn on with the rotation applied
+fn drawing_area(width: u32, height: u32, rotation: DisplayRotation) -> Rectangle {
+    let size = match rotation {
+        DisplayRotation::Rotate0 | DisplayRotation::Rotate180 => Size::new(width, height),
+        DisplayRotation::Rotate90 | DisplayRotation::Rotate270 => Size::new(height, width),
+    };
+    Rectangle::new(Point::zero(), size)
+}
+
+// Maps a drawing area that fits into the display to the buffer
+fn buffer_area(
+    area: Rectangle,
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirror: DisplayMirror,
+) -> Rectangle {
+    let corner = |p: Point| {
+        let (x, y) = find_rotation(p.x as u32, p.y as u32, width, height, rotation);
+        let (x, y) = find_mirror(x, y, width, height, mirror);
+        Point::new(x as i32, y as i32)
+    };
+    let (a, b) = (
+        corner(area.top_left),
+        corner(area.top_left + area.size - Size::new(1, 1)),
+    );
+    Rectangle::with_corners(a.component_min(b), a.component_max(b))
+}
+
+// Buffer step of one pixel to the right while drawing
+fn pixel_step(
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+    mirror: DisplayMirror,
+) -> (i32, i32) {
+    let map = |x| {
+        let (x, y) = find_rotation(x, 0, width, height, rotation);
+        let (x, y) = find_mirror(x, y, width, height, mirror);
+        (x as i32, y as i32)
+    };
+    let ((x0, y0), (x1, y1)) = (map(0), map(1));
+    (x1 - x0, y1 - y0)
+}
+
+// Bits of the pixels `from` to `to` (exclusive) of a byte
+pub(crate) fn byte_mask(from: u32, to: u32) -> u8 {
+    (0xFF >> from) & (0xFF_u16 << (8 - to)) as u8
+}
+
+// Widens the area to whole bytes
+fn align_to_bytes(area: Rectangle) -> Rectangle {
+    let left = area.top_left.x & !7;
+    let right = (area.top_left.x + area.size.width as i32 + 7) & !7;
+    Rectangle::new(
+        Point::new(left, area.top_left.y),
+        Size::new((right - left) as u32, area.size.height),
+    )
+}
+
+// Smallest rectangle containing both
+fn union(a: Rectangle, b: Rectangle) -> Rectangle {
+    let top_left = a.top_left.component_min(b.top_left);
+    let bottom_right = (a.top_left + a.size).component_max(b.top_left + b.size);
+    Rectangle::new(
+        top_left,
+        Size::new(
+            (bottom_right.x - top_left.x) as u32,
+            (bottom_right.y - top_left.y) as u32,
+        ),
+    )
 }
 
 // Checks if a pos is outside the defined display
-fn outside_display(p: Point, width: u32, height: u32, rotation: DisplayRotation) -> bool {
+pub(crate) fn outside_display(
+    p: Point,
+    width: u32,
+    height: u32,
+    rotation: DisplayRotation,
+) -> bool {
     if p.x < 0 || p.y < 0 {
         return true;
     }
@@ -216,12 +629,22 @@ fn find_rotation(x: u32, y: u32, width: u32, height: u32, rotation: DisplayRotat
     (nx, ny)
 }
 
+fn find_mirror(x: u32, y: u32, width: u32, height: u32, mirror: DisplayMirror) -> (u32, u32) {
+    match mirror {
+        DisplayMirror::None => (x, y),
+        DisplayMirror::Horizontal => (width - 1 - x, y),
+        DisplayMirror::Vertical => (x, height - 1 - y),
+        DisplayMirror::Both => (width - 1 - x, height - 1 - y),
+    }
+}
+
 #[rustfmt::skip]
 //returns index position in the u8-slice and the bit-position inside that u8
-fn find_position(x: u32, y: u32, width: u32, height: u32, rotation: DisplayRotation) -> (u32, u8) {
+pub(crate) fn find_position(x: u32, y: u32, width: u32, height: u32, rotation: DisplayRotation, mirror: DisplayMirror) -> (u32, u8) {
     let (nx, ny) = find_rotation(x, y, width, height, rotation);
+    let (nx, ny) = find_mirror(nx, ny, width, height, mirror);
     (
-        nx / 8 + ((width + 7) / 8) * ny,
+        nx / 8 + width.div_ceil(8) * ny,
         0x80 >> (nx % 8),
     )
 }
@@ -230,16 +653,22 @@ fn find_position(x: u32, y: u32, width: u32, height: u32, rotation: DisplayRotat
 /// is not divisible by 8.
 #[must_use]
 const fn buffer_len(width: usize, height: usize) -> usize {
-    (width + 7) / 8 * height
+    width.div_ceil(8) * height
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{find_position, outside_display, Display, Display1in54, DisplayRotation};
+    use super::{
+        find_position, outside_display, Display, Display1in54, DisplayMirror, DisplayRotation,
+    };
+    extern crate std;
+
     use crate::color::Black;
     use crate::color::Color;
-    use embedded_graphics::{prelude::*, primitives::Line};
+    use embedded_graphics::image::GetPixel;
+    use embedded_graphics::pixelcolor::BinaryColor;
     use embedded_graphics::primitives::PrimitiveStyle;
+    use embedded_graphics::{prelude::*, primitives::Line, primitives::Rectangle};
 
     #[test]
     fn buffer_clear() {
@@ -271,11 +700,12 @@ mod tests {
         let max_value = width / 8 * height;
         for x in 0..(width + height) {
             //limit x because it runs too long
-            for y in 0..(u32::max_value()) {
+            for y in 0..u32::MAX {
                 if outside_display(Point::new(x as i32, y as i32), width, height, rotation2) {
                     break;
                 } else {
-                    let (idx, _) = find_position(x, y, width, height, rotation2);
+                    let (idx, _) =
+                        find_position(x, y, width, height, rotation2, DisplayMirror::None);
                     assert!(idx < max_value);
                 }
             }
@@ -298,4 +728,218 @@ mod tests {
             assert_eq!(byte, Color::White.get_byte_value());
         }
     }
+
+    #[test]
+    fn graphics_mirror() {
+        let mut display = Display1in54::bw();
+        display.set_mirror(DisplayMirror::Both);
+
+        let _ = Line::new(Point::new(0, 0), Point::new(7, 0))
+            .into_styled(PrimitiveStyle::with_stroke(Black, 1))
+            .draw(&mut display);
+
+        let buffer = display.buffer();
+        let last = buffer.len() - 1;
+
+        assert_eq!(buffer[last], Color::Black.get_byte_value());
+
+        for &byte in buffer.iter().take(last) {
+            assert_eq!(byte, Color::White.get_byte_value());
+        }
+    }
+
+    #[test]
+    fn mirror_after_rotation() {
+        let mut display = Display1in54::bw();
+        display.set_rotation(DisplayRotation::Rotate90);
+        display.set_mirror(DisplayMirror::Horizontal);
+
+        // rotated to the top right corner, mirrored back to the top left
+        let _ = Pixel(Point::new(0, 0), Black).draw(&mut display);
+
+        let buffer = display.buffer();
+        assert_eq!(buffer[0], 0x7F);
+    }
+
+    // Every rotation and mirroring of both kinds of buffers
+    fn displays() -> std::vec::Vec<Display1in54> {
+        let mut displays = std::vec::Vec::new();
+        for &rotation in &[
+            DisplayRotation::Rotate0,
+            DisplayRotation::Rotate90,
+            DisplayRotation::Rotate180,
+            DisplayRotation::Rotate270,
+        ] {
+            for &mirror in &[
+                DisplayMirror::None,
+                DisplayMirror::Horizontal,
+                DisplayMirror::Vertical,
+                DisplayMirror::Both,
+            ] {
+                for mut display in [Display1in54::bw(), Display1in54::red()] {
+                    display.set_rotation(rotation);
+                    display.set_mirror(mirror);
+                    display.take_dirty();
+                    displays.push(display);
+                }
+            }
+        }
+        displays
+    }
+
+    // Draws with `fast` and pixel by pixel, and compares the buffers and changed areas
+    fn assert_same(
+        fast: impl Fn(&mut Display1in54),
+        pixels: impl Fn() -> std::vec::Vec<Pixel<BinaryColor>>,
+    ) {
+        for mut display in displays() {
+            let mut expected = Display1in54 { ..display };
+            for pixel in pixels() {
+                let _ = pixel.draw(&mut expected);
+            }
+            fast(&mut display);
+            assert_eq!(&display.buffer[..], &expected.buffer[..]);
+            assert_eq!(display.dirty_area(), expected.dirty_area());
+        }
+    }
+
+    #[test]
+    fn fast_paths_match_pixels() {
+        let area = Rectangle::new(Point::new(-3, 5), Size::new(37, 20));
+        assert_same(
+            |display| display.fill_solid(&area, Black).unwrap(),
+            || area.points().map(|p| Pixel(p, Black)).collect(),
+        );
+
+        let area = Rectangle::new(Point::new(187, -2), Size::new(21, 9));
+        let color = |i: usize| BinaryColor::from(i % 3 == 0 || i % 7 == 0);
+        assert_same(
+            |display| display.fill_contiguous(&area, (0..).map(color)).unwrap(),
+            || {
+                area.points()
+                    .enumerate()
+                    .map(|(i, p)| Pixel(p, color(i)))
+                    .collect()
+            },
+        );
+
+        let all = Rectangle::new(Point::zero(), Size::new(200, 200));
+        assert_same(
+            |display| display.clear(Black).unwrap(),
+            || all.points().map(|p| Pixel(p, Black)).collect(),
+        );
+    }
+
+    #[test]
+    fn pixels_read_back() {
+        let points = [Point::new(0, 0), Point::new(17, 3), Point::new(199, 3)];
+        for mut display in displays() {
+            for &p in &points {
+                let _ = Pixel(p, BinaryColor::On).draw(&mut display);
+            }
+
+            assert_eq!(display.pixel(Point::new(17, 3)), Some(BinaryColor::On));
+            assert_eq!(display.pixel(Point::new(18, 3)), Some(BinaryColor::Off));
+            assert_eq!(display.pixel(Point::new(200, 3)), None);
+            assert_eq!(display.set_pixels().collect::<std::vec::Vec<_>>(), points);
+
+            let row = display.rows().nth(3).unwrap();
+            assert_eq!(row.y(), 3);
+            let set: std::vec::Vec<_> = row
+                .enumerate()
+                .filter(|(_, color)| color.is_on())
+                .map(|(x, _)| x)
+                .collect();
+            assert_eq!(set, [17, 199]);
+        }
+    }
+
+    #[test]
+    fn dirty_area() {
+        let mut display = Display1in54::bw();
+        assert_eq!(
+            display.take_dirty(),
+            Some(Rectangle::new(Point::zero(), Size::new(200, 200)))
+        );
+        assert_eq!(display.dirty_area(), None);
+
+        display.set_rotation(DisplayRotation::Rotate90);
+        let _ = Pixel(Point::new(10, 3), Black).draw(&mut display);
+        let _ = Pixel(Point::new(12, 20), Black).draw(&mut display);
+
+        // rotated to buffer x 196 and 179, y 10 and 12, widened to bytes
+        assert_eq!(
+            display.take_dirty(),
+            Some(Rectangle::new(Point::new(176, 10), Size::new(24, 3)))
+        );
+        assert_eq!(display.take_dirty(), None);
+    }
+
+    // Implements only what the trait requires
+    struct Minimal {
+        buffer: [u8; 4 * 16],
+    }
+
+    impl Dimensions for Minimal {
+        fn bounding_box(&self) -> Rectangle {
+            Rectangle::new(Point::zero(), Size::new(32, 16))
+        }
+    }
+
+    impl DrawTarget for Minimal {
+        type Color = BinaryColor;
+        type Error = core::convert::Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            for pixel in pixels {
+                self.draw_helper(32, 16, pixel)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl Display for Minimal {
+        fn buffer(&self) -> &[u8] {
+            &self.buffer
+        }
+
+        fn get_mut_buffer(&mut self) -> &mut [u8] {
+            &mut self.buffer
+        }
+
+        fn set_rotation(&mut self, _rotation: DisplayRotation) {}
+
+        fn rotation(&self) -> DisplayRotation {
+            DisplayRotation::Rotate0
+        }
+
+        fn is_inverted(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn default_methods() {
+        let mut display = Minimal { buffer: [0; 64] };
+        let _ = Pixel(Point::new(9, 1), BinaryColor::On).draw(&mut display);
+        assert_eq!(display.buffer()[5], 0x40);
+
+        // without tracking everything is dirty, all the time
+        let whole = Some(Rectangle::new(Point::zero(), Size::new(32, 16)));
+        assert_eq!(display.take_dirty(), whole);
+        assert_eq!(display.take_dirty(), whole);
+
+        assert!(display.set_mirror(DisplayMirror::None));
+        assert_eq!(display.mirror(), DisplayMirror::None);
+    }
+
+    #[test]
+    fn default_mirror_is_refused() {
+        let mut display = Minimal { buffer: [0; 64] };
+        assert!(!display.set_mirror(DisplayMirror::Vertical));
+        assert_eq!(display.mirror(), DisplayMirror::None);
+    }
 }
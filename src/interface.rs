@@ -1,128 +1,406 @@
-//! Display interface using SPI
+//! Transports to the controller
+//!
+//! The driver sends its commands through any [`WriteOnlyDataCommand`], for example the
+//! `SPIInterface` of `display-interface-spi`. [`SpiInterface`] is the crate's own 4-wire SPI
+//! transport, it can also read from the controller ([`ReadData`]) which the read back, OTP and
+//! diagnostic methods of the driver need. [`ThreeWireSpiInterface`] talks to a controller
+//! strapped for 3-wire SPI, without a Data/Command pin.
 
 use core::fmt::Debug;
-use core::marker::PhantomData;
-use embedded_hal::delay::DelayNs;
-use embedded_hal::digital::{InputPin, OutputPin};
-use embedded_hal::spi::SpiDevice;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::spi::{Operation, SpiDevice};
 
-const RESET_DELAY_MS: u32 = 10;
+pub use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
 
-/// The Connection Interface of all (?) Waveshare EPD-Devices
+/// Default maximum number of bytes sent in one SPI write
 ///
-pub(crate) struct DisplayInterface<SPI, CS, BUSY, DC, RST> {
-    /// SPI
-    _spi: PhantomData<SPI>,
-    /// CS for SPI
-    cs: CS,
-    /// Low for busy, Wait until display is ready!
-    busy: BUSY,
+/// Linux has a default limit of 4096 bytes per spi transfer, see
+/// <https://raspberrypi.stackexchange.com/questions/65595/spi-transfer-fails-with-buffer-size-greater-than-4096>
+pub const DEFAULT_MAX_CHUNK_SIZE: usize = 4096;
+
+/// Size of the stack buffer used to stream repeated or transformed bytes
+const STACK_BUFFER_SIZE: usize = 128;
+
+/// Bytes sent per 3-wire SPI write, a whole number of 9-bit frames packed into words
+const PACKED_BUFFER_SIZE: usize = STACK_BUFFER_SIZE * 9 / 8;
+
+/// Error of a transfer that reads from the controller
+#[derive(Clone, Debug)]
+pub enum ReadError {
+    /// Sending the command failed
+    Display(DisplayError),
+    /// Unable to read from bus
+    BusReadError,
+}
+
+impl From<DisplayError> for ReadError {
+    fn from(error: DisplayError) -> Self {
+        ReadError::Display(error)
+    }
+}
+
+/// A transport that can read data back from the controller
+pub trait ReadData {
+    /// Read `buffer.len()` bytes the controller returns for the last command
+    fn read_data(&mut self, buffer: &mut [u8]) -> Result<(), ReadError>;
+
+    /// Read RAM the controller returns for the last command into `buffer`
+    ///
+    /// The controller sends a dummy byte before the RAM content. It has to be read in the same
+    /// transaction as `buffer`, the read ends when chip select goes high.
+    fn read_ram_data(&mut self, buffer: &mut [u8]) -> Result<(), ReadError>;
+}
+
+/// 4-wire SPI: an [`SpiDevice`], which owns chip select, and a Data/Command pin
+pub struct SpiInterface<SPI, DC> {
+    spi: SPI,
     /// Data/Command Control Pin (High for data, Low for command)
     dc: DC,
-    /// Pin for Reseting
-    rst: RST,
+    /// Maximum number of bytes in one SPI write
+    max_chunk_size: usize,
 }
 
-impl<SPI, CS, BUSY, DC, RST> DisplayInterface<SPI, CS, BUSY, DC, RST>
+impl<SPI, DC> SpiInterface<SPI, DC>
 where
     SPI: SpiDevice,
-    CS: OutputPin,
-    CS::Error: Debug,
-    BUSY: InputPin,
     DC: OutputPin,
     DC::Error: Debug,
-    RST: OutputPin,
-    RST::Error: Debug,
 {
-    /// Create and initialize display
-    pub fn new(cs: CS, busy: BUSY, dc: DC, rst: RST) -> Self {
-        DisplayInterface {
-            _spi: PhantomData::default(),
-            cs,
-            busy,
+    /// Create the interface
+    pub fn new(spi: SPI, dc: DC) -> Self {
+        SpiInterface {
+            spi,
             dc,
-            rst,
+            max_chunk_size: DEFAULT_MAX_CHUNK_SIZE,
         }
     }
 
-    /// Basic function for sending commands
-    pub(crate) fn cmd(&mut self, spi: &mut SPI, command: u8) -> Result<(), SPI::Error> {
+    /// Set the maximum number of bytes sent in one SPI write
+    ///
+    /// Defaults to [`DEFAULT_MAX_CHUNK_SIZE`]. Lower it to fit the DMA or driver limits of your
+    /// target.
+    pub fn set_max_chunk_size(&mut self, max_chunk_size: usize) {
+        assert!(max_chunk_size > 0);
+        self.max_chunk_size = max_chunk_size;
+    }
+
+    /// Return the SPI device and the Data/Command pin
+    pub fn release(self) -> (SPI, DC) {
+        (self.spi, self.dc)
+    }
+
+    // spi write helper/abstraction function
+    fn write(&mut self, data: DataFormat<'_>) -> Result<(), DisplayError> {
+        match data {
+            DataFormat::U8(data) => {
+                // transfer spi data in chunks the target can handle
+                for data_chunk in data.chunks(self.max_chunk_size) {
+                    self.write_chunk(data_chunk)?;
+                }
+                Ok(())
+            }
+            DataFormat::U8Iter(iter) => {
+                let mut buffer = [0; STACK_BUFFER_SIZE];
+                let chunk_size = STACK_BUFFER_SIZE.min(self.max_chunk_size);
+                loop {
+                    let mut len = 0;
+                    for (dst, src) in buffer[..chunk_size].iter_mut().zip(&mut *iter) {
+                        *dst = src;
+                        len += 1;
+                    }
+                    if len == 0 {
+                        return Ok(());
+                    }
+                    self.write_chunk(&buffer[..len])?;
+                }
+            }
+            _ => Err(DisplayError::DataFormatNotImplemented),
+        }
+    }
+
+    fn write_chunk(&mut self, data: &[u8]) -> Result<(), DisplayError> {
+        self.spi
+            .write(data)
+            .map_err(|_| DisplayError::BusWriteError)
+    }
+}
+
+impl<SPI, DC> WriteOnlyDataCommand for SpiInterface<SPI, DC>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+    DC::Error: Debug,
+{
+    fn send_commands(&mut self, cmd: DataFormat<'_>) -> Result<(), DisplayError> {
         // low for commands
-        self.dc.set_low().unwrap();
+        self.dc.set_low().map_err(|_| DisplayError::DCError)?;
+        self.write(cmd)
+    }
 
-        // Transfer the command over spi
-        self.write(spi, &[command])
+    fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        // high for data
+        self.dc.set_high().map_err(|_| DisplayError::DCError)?;
+        self.write(buf)
     }
+}
 
-    /// Basic function for sending an array of u8-values of data over spi
-    pub(crate) fn data(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
+impl<SPI, DC> ReadData for SpiInterface<SPI, DC>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+    DC::Error: Debug,
+{
+    fn read_data(&mut self, buffer: &mut [u8]) -> Result<(), ReadError> {
         // high for data
-        self.dc.set_high().unwrap();
+        self.dc.set_high().map_err(|_| DisplayError::DCError)?;
+        self.spi.read(buffer).map_err(|_| ReadError::BusReadError)
+    }
 
-        // Transfer data (u8-array) over spi
-        self.write(spi, data)
+    fn read_ram_data(&mut self, buffer: &mut [u8]) -> Result<(), ReadError> {
+        self.dc.set_high().map_err(|_| DisplayError::DCError)?;
+        self.spi
+            .transaction(&mut [Operation::Read(&mut [0]), Operation::Read(buffer)])
+            .map_err(|_| ReadError::BusReadError)
     }
+}
 
-    /// Basic function for sending a command and the data belonging to it.
-    pub(crate) fn cmd_with_data(
-        &mut self,
-        spi: &mut SPI,
-        command: u8,
-        data: &[u8],
-    ) -> Result<(), SPI::Error> {
-        self.cmd(spi, command)?;
-        self.data(spi, data)
+/// 3-wire SPI: an [`SpiDevice`] and no Data/Command pin
+///
+/// With BS1 tied high the controller expects 9-bit frames, the D/C bit followed by the byte.
+/// The frames are packed back to back into 8-bit words, 8 bytes take 9 words. The bits left
+/// over at the end of a write do not make up a frame and are dropped by the controller when
+/// chip select goes high, which the [`SpiDevice`] does after every write. Bit-banged pins
+/// work through any `SpiDevice` implementation driving them.
+///
+/// Reads clock the data out in plain 8-bit words.
+pub struct ThreeWireSpiInterface<SPI> {
+    spi: SPI,
+}
+
+impl<SPI> ThreeWireSpiInterface<SPI>
+where
+    SPI: SpiDevice,
+{
+    /// Create the interface
+    pub fn new(spi: SPI) -> Self {
+        ThreeWireSpiInterface { spi }
     }
 
-    /// Basic function for sending the same byte of data (one u8) multiple times over spi
-    /// Used for setting one color for the whole frame
-    pub(crate) fn data_x_times(
+    /// Return the SPI device
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    fn write(&mut self, dc: bool, data: DataFormat<'_>) -> Result<(), DisplayError> {
+        match data {
+            DataFormat::U8(data) => self.write_frames(dc, &mut data.iter().copied()),
+            DataFormat::U8Iter(iter) => self.write_frames(dc, iter),
+            _ => Err(DisplayError::DataFormatNotImplemented),
+        }
+    }
+
+    fn write_frames(
         &mut self,
-        spi: &mut SPI,
-        val: u8,
-        repetitions: u32,
-    ) -> Result<(), SPI::Error> {
-        // high for data
-        let _ = self.dc.set_high();
-        // Transfer data (u8) over spi
-        for _ in 0..repetitions {
-            self.write(spi, &[val])?;
+        dc: bool,
+        bytes: &mut dyn Iterator<Item = u8>,
+    ) -> Result<(), DisplayError> {
+        let mut buffer = [0; PACKED_BUFFER_SIZE];
+        loop {
+            buffer.fill(0);
+            let mut frames = 0;
+            for byte in bytes.take(STACK_BUFFER_SIZE) {
+                // a frame starting at `offset` ends in the next word at the latest
+                let (index, offset) = (frames * 9 / 8, frames * 9 % 8);
+                let frame = (u16::from(dc) << 8 | u16::from(byte)) << (7 - offset);
+                buffer[index] |= (frame >> 8) as u8;
+                buffer[index + 1] |= frame as u8;
+                frames += 1;
+            }
+            if frames == 0 {
+                return Ok(());
+            }
+            self.spi
+                .write(&buffer[..(frames * 9).div_ceil(8)])
+                .map_err(|_| DisplayError::BusWriteError)?;
+            if frames < STACK_BUFFER_SIZE {
+                return Ok(());
+            }
         }
-        Ok(())
     }
+}
 
-    /// Waits until device isn't busy anymore (busy == HIGH)
-    pub(crate) fn wait_until_idle(&mut self) {
-        while self.busy.is_high().unwrap_or(true) {}
+impl<SPI> WriteOnlyDataCommand for ThreeWireSpiInterface<SPI>
+where
+    SPI: SpiDevice,
+{
+    fn send_commands(&mut self, cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+        // D/C bit low for commands
+        self.write(false, cmd)
     }
 
-    /// Resets the device.
-    pub(crate) fn reset<DELAY: DelayNs>(&mut self, delay: &mut DELAY) {
-        self.rst.set_low().unwrap();
-        delay.delay_ms(RESET_DELAY_MS);
-        self.rst.set_high().unwrap();
-        delay.delay_ms(RESET_DELAY_MS);
+    fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        // D/C bit high for data
+        self.write(true, buf)
     }
+}
 
-    // spi write helper/abstraction function
-    fn write(&mut self, spi: &mut SPI, data: &[u8]) -> Result<(), SPI::Error> {
-        // activate spi with cs low
-        self.cs.set_low().unwrap();
-
-        // transfer spi data
-        // Be careful!! Linux has a default limit of 4096 bytes per spi transfer
-        // see https://raspberrypi.stackexchange.com/questions/65595/spi-transfer-fails-with-buffer-size-greater-than-4096
-        if cfg!(target_os = "linux") {
-            for data_chunk in data.chunks(4096) {
-                spi.write(data_chunk)?;
-            }
-        } else {
-            spi.write(data)?;
+impl<SPI> ReadData for ThreeWireSpiInterface<SPI>
+where
+    SPI: SpiDevice,
+{
+    fn read_data(&mut self, buffer: &mut [u8]) -> Result<(), ReadError> {
+        self.spi.read(buffer).map_err(|_| ReadError::BusReadError)
+    }
+
+    fn read_ram_data(&mut self, buffer: &mut [u8]) -> Result<(), ReadError> {
+        self.spi
+            .transaction(&mut [Operation::Read(&mut [0]), Operation::Read(buffer)])
+            .map_err(|_| ReadError::BusReadError)
+    }
+}
+
+/// The commands the driver builds on top of a transport
+pub(crate) trait Commands: WriteOnlyDataCommand {
+    /// Basic function for sending commands
+    fn cmd(&mut self, command: u8) -> Result<(), DisplayError> {
+        self.send_commands(DataFormat::U8(&[command]))
+    }
+
+    /// Basic function for sending an array of u8-values of data
+    fn data(&mut self, data: &[u8]) -> Result<(), DisplayError> {
+        self.send_data(DataFormat::U8(data))
+    }
+
+    /// Basic function for sending a command and the data belonging to it.
+    fn cmd_with_data(&mut self, command: u8, data: &[u8]) -> Result<(), DisplayError> {
+        self.cmd(command)?;
+        self.data(data)
+    }
+
+    /// Basic function for sending the same byte of data (one u8) multiple times
+    /// Used for setting one color for the whole frame
+    fn data_x_times(&mut self, val: u8, repetitions: u32) -> Result<(), DisplayError> {
+        let mut bytes = core::iter::repeat(val).take(repetitions as usize);
+        self.send_data(DataFormat::U8Iter(&mut bytes))
+    }
+
+    /// Basic function for sending data with every byte passed through `map` first
+    ///
+    /// The mapped bytes are streamed to the transport so `data` is never copied whole.
+    fn data_mapped(&mut self, data: &[u8], map: fn(u8) -> u8) -> Result<(), DisplayError> {
+        let mut bytes = data.iter().map(|&byte| map(byte));
+        self.send_data(DataFormat::U8Iter(&mut bytes))
+    }
+}
+
+impl<DI: WriteOnlyDataCommand> Commands for DI {}
+
+/// The reads the driver builds on top of a transport
+pub(crate) trait ReadCommands: Commands + ReadData {
+    /// Basic function for sending a command and reading the data it returns
+    fn cmd_read(&mut self, command: u8, buffer: &mut [u8]) -> Result<(), ReadError> {
+        self.cmd(command)?;
+        self.read_data(buffer)
+    }
+
+    /// Basic function for sending a RAM read command and reading the RAM into `buffer`
+    fn cmd_read_ram(&mut self, command: u8, buffer: &mut [u8]) -> Result<(), ReadError> {
+        self.cmd(command)?;
+        self.read_ram_data(buffer)
+    }
+}
+
+impl<DI: Commands + ReadData> ReadCommands for DI {}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::{
+        Commands, DataFormat, DisplayError, SpiInterface, ThreeWireSpiInterface,
+        WriteOnlyDataCommand,
+    };
+    use crate::mock::{Dc, Sim, Spi};
+
+    // A transport recording the length of every data write
+    #[derive(Default)]
+    struct Writes(Vec<usize>);
+
+    impl WriteOnlyDataCommand for Writes {
+        fn send_commands(&mut self, _cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+            Ok(())
         }
 
-        // deativate spi with cs high
-        self.cs.set_high().unwrap();
+        fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+            let len = match buf {
+                DataFormat::U8(data) => data.len(),
+                DataFormat::U8Iter(iter) => iter.count(),
+                _ => return Err(DisplayError::DataFormatNotImplemented),
+            };
+            self.0.push(len);
+            Ok(())
+        }
+    }
+
+    fn interface(sim: &Sim) -> SpiInterface<Spi, Dc> {
+        SpiInterface::new(sim.spi(), sim.dc())
+    }
+
+    #[test]
+    fn repeated_bytes_are_streamed_in_chunks() {
+        let sim = Sim::new();
+        let mut interface = interface(&sim);
+
+        interface.cmd(0x24).unwrap();
+        interface.data_x_times(0xAA, 5000).unwrap();
+
+        // one transaction for the command and 128 bytes per data write
+        assert_eq!(sim.transactions(), 1 + 5000_usize.div_ceil(128));
+        let data = sim.data_for(0x24).unwrap();
+        assert_eq!(data.len(), 5000);
+        assert!(data.iter().all(|&b| b == 0xAA));
+    }
+
+    #[test]
+    fn generated_bytes_are_one_write() {
+        let mut writes = Writes::default();
+        writes.data_x_times(0xAA, 5000).unwrap();
+        writes.data_mapped(&[0x01; 300], u8::reverse_bits).unwrap();
+
+        // the transport decides how to split them
+        assert_eq!(writes.0, [5000, 300]);
+    }
+
+    #[test]
+    fn writes_respect_max_chunk_size() {
+        let sim = Sim::new();
+        let mut interface = interface(&sim);
+        interface.set_max_chunk_size(1000);
+
+        interface.cmd_with_data(0x26, &[0x55; 5000]).unwrap();
+        assert_eq!(sim.transactions(), 1 + 5);
+
+        sim.clear();
+        interface.cmd(0x24).unwrap();
+        interface.data_x_times(0x00, 100).unwrap();
+        assert_eq!(sim.transactions(), 2);
+        assert_eq!(sim.data_for(0x24).unwrap().len(), 100);
+    }
+
+    #[test]
+    fn three_wire_packs_9_bit_frames() {
+        let sim = Sim::three_wire();
+        let mut interface = ThreeWireSpiInterface::new(sim.spi());
+
+        let data: [u8; 300] = core::array::from_fn(|i| i as u8);
+        interface.cmd_with_data(0x24, &data).unwrap();
+        interface.cmd(0x20).unwrap();
 
-        Ok(())
+        // 128 frames per write, each write is sent with chip select of its own
+        assert_eq!(sim.transactions(), 1 + 3 + 1);
+        assert_eq!(sim.data_for(0x24).unwrap(), data);
+        assert_eq!(sim.data_for(0x20).unwrap(), []);
     }
 }
@@ -13,12 +13,26 @@
 //! display something you:
 //!
 //! 1. first create a buffer (either b/w or red) and draw things onto it, preferably
-//! with [`embedded_graphics`](https://github.com/jamwaffles/embedded-graphics).
+//!    with [`embedded_graphics`](https://github.com/jamwaffles/embedded-graphics).
 //! 1. then send the frame to the display driver using [`driver::Ssd1681::update_bw_frame`] or
 //!    [`driver::Ssd1681::update_red_frame`]
-//! 1. then kick off a display update using [`driver::Ssd1681::update_bw_frame`] or
-//!     [`driver::Ssd1681::update_red_frame`]
+//! 1. then kick off a display update using [`driver::Ssd1681::display_frame`]
 //!
+//! [`screen::Screen`] does all of this for you: it owns the driver and both buffers, and only sends the planes that changed.
+//!
+//! Without the RAM for two frame buffers draw into the SPI SRAM of the breakout instead, see
+//! [`sram::SramDisplay`], or draw the frame a few rows at a time with [`band::BandRenderer`].
+//! With a transport that can read, the driver can also be drawn on directly, see [`direct`].
+//!
+//! ### Other controllers
+//! The SSD1680, SSD1608 and SSD1675B on 2.13" and 2.9" panels share the command set, see
+//! [`controller`] and [`driver::Ssd1681::with_interface`].
+//!
+//! ### Hardware rotation
+//! [`graphics::DisplayRotation`] rotates in software while drawing. Rotations that keep whole
+//! bytes intact (0 and 180 degrees) can instead be left to the controller: keep drawing in
+//! `Rotate0` and pass [`graphics::DisplayRotation::data_entry_mode`] to
+//! [`driver::Ssd1681::set_data_entry_mode`].
 //!
 #![no_std]
 #![deny(missing_docs)]
@@ -29,11 +43,31 @@
 #![allow(clippy::missing_errors_doc)]
 
 pub mod color;
+pub mod controller;
 pub mod driver;
+pub mod otp;
+pub mod pin;
+pub mod ram;
+pub mod refresh;
+pub mod status;
+pub mod typestate;
+pub mod voltage;
+
+#[cfg(feature = "graphics")]
+pub mod band;
+
+#[cfg(feature = "graphics")]
+pub mod direct;
 
 #[cfg(feature = "graphics")]
 pub mod graphics;
 
+#[cfg(feature = "graphics")]
+pub mod screen;
+
+#[cfg(feature = "graphics")]
+pub mod sram;
+
 mod cmd {
     pub const SW_RESET: u8 = 0x12;
     pub const DRIVER_CONTROL: u8 = 0x01;
@@ -43,36 +77,87 @@ mod cmd {
     pub const BORDER_WAVEFORM_CONTROL: u8 = 0x3C;
     pub const TEMP_CONTROL: u8 = 0x18;
 
+    // Analog
+    pub const GATE_VOLTAGE: u8 = 0x03;
+    pub const SOURCE_VOLTAGE: u8 = 0x04;
+    pub const BOOSTER_SOFT_START: u8 = 0x0C;
+    pub const WRITE_VCOM: u8 = 0x2C;
+
+    // Diagnostics
+    pub const HV_READY_DETECTION: u8 = 0x14;
+    pub const VCI_DETECTION: u8 = 0x15;
+    pub const STATUS_BIT_READ: u8 = 0x2F;
+
+    // Read back
+    pub const READ_RAM_OPTION: u8 = 0x41;
+    pub const READ_RAM: u8 = 0x27;
+    pub const CRC_CALCULATION: u8 = 0x34;
+    pub const CRC_STATUS_READ: u8 = 0x35;
+
+    // OTP
+    pub const READ_DISPLAY_OPTION: u8 = 0x2D;
+    pub const READ_USER_ID: u8 = 0x2E;
+    pub const WRITE_DISPLAY_OPTION: u8 = 0x37;
+    pub const WRITE_USER_ID: u8 = 0x38;
+    pub const WRITE_LUT: u8 = 0x32;
+    pub const PROGRAM_WS_OTP: u8 = 0x30;
+    pub const LOAD_WS_OTP: u8 = 0x31;
+    pub const PROGRAM_OTP_SELECTION: u8 = 0x36;
+
     // Update
     pub const SET_RAMX_COUNTER: u8 = 0x4E;
     pub const SET_RAMY_COUNTER: u8 = 0x4F;
     pub const WRITE_BW_DATA: u8 = 0x24;
     pub const WRITE_RED_DATA: u8 = 0x26;
+    pub const AUTO_WRITE_RED: u8 = 0x46;
+    pub const AUTO_WRITE_BW: u8 = 0x47;
     pub const UPDATE_DISPLAY_CTRL2: u8 = 0x22;
     pub const MASTER_ACTIVATE: u8 = 0x20;
+
+    // Power
+    pub const DEEP_SLEEP_MODE: u8 = 0x10;
 }
 
 mod flag {
+    pub const DATA_ENTRY_DECRY_DECRX: u8 = 0b00;
+    pub const DATA_ENTRY_DECRY_INCRX: u8 = 0b01;
+    pub const DATA_ENTRY_INCRY_DECRX: u8 = 0b10;
     pub const DATA_ENTRY_INCRY_INCRX: u8 = 0b11;
+    pub const GATE_SCAN_GD: u8 = 0b100;
+    pub const GATE_SCAN_SM: u8 = 0b010;
+    pub const GATE_SCAN_TB: u8 = 0b001;
     pub const INTERNAL_TEMP_SENSOR: u8 = 0x80;
     pub const BORDER_WAVEFORM_FOLLOW_LUT: u8 = 0b0100;
     pub const BORDER_WAVEFORM_LUT1: u8 = 0b0001;
     pub const DISPLAY_MODE_1: u8 = 0xF7;
+    pub const DISPLAY_MODE_2: u8 = 0xFF;
+    pub const DISPLAY_WITH_LUT_REGISTER: u8 = 0xC7;
+    pub const ENABLE_CLOCK_ANALOG: u8 = 0xC0;
+    pub const DISABLE_CLOCK_ANALOG: u8 = 0x03;
+    pub const DEEP_SLEEP_MODE_1: u8 = 0b01;
+    pub const DEEP_SLEEP_MODE_2: u8 = 0b11;
 }
 
-/// Maximum display height this driver supports
+/// Height of the 1.54" SSD1681 panel, other panels are described by a
+/// [`PanelSize`](controller::PanelSize)
 pub const HEIGHT: u8 = 200;
 
-/// Maximum display width this driver supports
+/// Width of the 1.54" SSD1681 panel, other panels are described by a
+/// [`PanelSize`](controller::PanelSize)
 pub const WIDTH: u8 = 200;
 
 pub mod interface;
 
+#[cfg(test)]
+mod mock;
+
 /// Useful exports
 pub mod prelude {
-    pub use crate::color::Color;
+    pub use crate::color::{Color, TriColor};
     pub use crate::driver::Ssd1681;
 
     #[cfg(feature = "graphics")]
-    pub use crate::graphics::{Display, Display1in54, DisplayRotation};
+    pub use crate::graphics::{Display, Display1in54, DisplayMirror, DisplayRotation};
+    #[cfg(feature = "graphics")]
+    pub use crate::screen::Screen;
 }
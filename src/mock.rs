@@ -0,0 +1,400 @@
+//! Simulated bus and pins shared by the unit tests
+
+extern crate std;
+
+use core::convert::Infallible;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::vec::Vec;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{ErrorType as PinErrorType, InputPin, OutputPin};
+use embedded_hal::spi::{ErrorType as SpiErrorType, Operation, SpiDevice};
+
+use crate::driver::Ssd1681;
+use crate::interface::SpiInterface;
+use crate::refresh::Clock;
+#[cfg(feature = "graphics")]
+use crate::screen::Screen;
+
+/// Everything the simulated controller has seen on the wire
+#[derive(Default)]
+pub(crate) struct State {
+    /// Level of the D/C pin, high for data
+    pub(crate) dc: bool,
+    /// Number of SPI transactions
+    pub(crate) transactions: usize,
+    /// Number of SPI transactions that read
+    pub(crate) read_transactions: usize,
+    /// Commands with the data bytes that followed them
+    pub(crate) commands: Vec<(u8, Vec<u8>)>,
+    /// Bytes returned when reading after a command
+    pub(crate) responses: HashMap<u8, Vec<u8>>,
+    /// Bytes read since the last command
+    pub(crate) read_position: usize,
+    /// Level of the BUSY pin
+    pub(crate) busy: bool,
+    /// Writes are 9-bit frames with a leading D/C bit instead of following the D/C pin
+    pub(crate) three_wire: bool,
+}
+
+impl State {
+    fn command(&mut self, command: u8) {
+        self.commands.push((command, Vec::new()));
+        self.read_position = 0;
+    }
+
+    fn data(&mut self, bytes: &[u8]) {
+        if let Some((_, data)) = self.commands.last_mut() {
+            data.extend_from_slice(bytes);
+        }
+    }
+
+    // Split the bits of one transaction into 9-bit frames, a partial frame at the end is dropped
+    fn three_wire_frames(&mut self, bytes: &[u8]) {
+        let bits: Vec<bool> = bytes
+            .iter()
+            .flat_map(|byte| (0..8).rev().map(move |i| byte & (1 << i) != 0))
+            .collect();
+        for frame in bits.chunks_exact(9) {
+            let byte = frame[1..]
+                .iter()
+                .fold(0, |acc, &bit| acc << 1 | u8::from(bit));
+            if frame[0] {
+                self.data(&[byte]);
+            } else {
+                self.command(byte);
+            }
+        }
+    }
+}
+
+/// Handle to the shared state
+#[derive(Clone, Default)]
+pub(crate) struct Sim(Rc<RefCell<State>>);
+
+impl Sim {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Simulate a controller in 3-wire SPI mode
+    pub(crate) fn three_wire() -> Self {
+        let sim = Self::default();
+        sim.0.borrow_mut().three_wire = true;
+        sim
+    }
+
+    /// Driver initialised on the simulated bus, with a BUSY pin that is always idle
+    pub(crate) fn driver(&self) -> Ssd1681<SpiInterface<Spi, Dc>, Idle, Pin> {
+        Ssd1681::new(self.spi(), Idle, self.dc(), Pin, &mut NoDelay).unwrap()
+    }
+
+    /// Screen around [`driver`](Self::driver)
+    #[cfg(feature = "graphics")]
+    pub(crate) fn screen(&self) -> Screen<SpiInterface<Spi, Dc>, Idle, Pin> {
+        Screen::new(self.spi(), Idle, self.dc(), Pin, &mut NoDelay).unwrap()
+    }
+
+    pub(crate) fn spi(&self) -> Spi {
+        Spi(self.clone())
+    }
+
+    pub(crate) fn dc(&self) -> Dc {
+        Dc(self.clone())
+    }
+
+    pub(crate) fn busy(&self) -> Busy {
+        Busy(self.clone())
+    }
+
+    pub(crate) fn set_busy(&self, busy: bool) {
+        self.0.borrow_mut().busy = busy;
+    }
+
+    pub(crate) fn transactions(&self) -> usize {
+        self.0.borrow().transactions
+    }
+
+    pub(crate) fn read_transactions(&self) -> usize {
+        self.0.borrow().read_transactions
+    }
+
+    /// Data sent after the last occurrence of `command`
+    pub(crate) fn data_for(&self, command: u8) -> Option<Vec<u8>> {
+        self.0
+            .borrow()
+            .commands
+            .iter()
+            .rev()
+            .find(|(c, _)| *c == command)
+            .map(|(_, data)| data.clone())
+    }
+
+    /// Every command sent, with the data bytes that followed it
+    pub(crate) fn commands(&self) -> Vec<(u8, Vec<u8>)> {
+        self.0.borrow().commands.clone()
+    }
+
+    /// Answer reads after `command` with `bytes`
+    pub(crate) fn respond(&self, command: u8, bytes: &[u8]) {
+        self.0
+            .borrow_mut()
+            .responses
+            .insert(command, bytes.to_vec());
+    }
+
+    pub(crate) fn clear(&self) {
+        let mut state = self.0.borrow_mut();
+        state.transactions = 0;
+        state.read_transactions = 0;
+        state.commands.clear();
+    }
+}
+
+/// Simulated SPI device
+pub(crate) struct Spi(Sim);
+
+impl SpiErrorType for Spi {
+    type Error = Infallible;
+}
+
+impl SpiDevice for Spi {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        let mut state = (self.0).0.borrow_mut();
+        state.transactions += 1;
+        if operations.iter().any(|op| matches!(op, Operation::Read(_))) {
+            state.read_transactions += 1;
+        }
+        if state.three_wire {
+            let mut written = Vec::new();
+            for op in operations.iter() {
+                if let Operation::Write(bytes) = op {
+                    written.extend_from_slice(bytes);
+                }
+            }
+            state.three_wire_frames(&written);
+        }
+        for op in operations {
+            match op {
+                Operation::Write(_) if state.three_wire => {}
+                Operation::Write(bytes) if state.dc => state.data(bytes),
+                Operation::Write(bytes) => {
+                    for &command in bytes.iter() {
+                        state.command(command);
+                    }
+                }
+                Operation::Read(buffer) => {
+                    let command = state.commands.last().map(|(c, _)| *c);
+                    let response = command.and_then(|c| state.responses.get(&c));
+                    let position = state.read_position;
+                    for (i, byte) in buffer.iter_mut().enumerate() {
+                        *byte = response
+                            .and_then(|r| r.get(position + i).copied())
+                            .unwrap_or(0xFF);
+                    }
+                    state.read_position += buffer.len();
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Simulated D/C pin
+pub(crate) struct Dc(Sim);
+
+impl PinErrorType for Dc {
+    type Error = Infallible;
+}
+
+impl OutputPin for Dc {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        (self.0).0.borrow_mut().dc = false;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        (self.0).0.borrow_mut().dc = true;
+        Ok(())
+    }
+}
+
+/// Output pin that ignores everything
+pub(crate) struct Pin;
+
+impl PinErrorType for Pin {
+    type Error = Infallible;
+}
+
+impl OutputPin for Pin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// BUSY pin of a controller that is always idle
+pub(crate) struct Idle;
+
+impl PinErrorType for Idle {
+    type Error = Infallible;
+}
+
+impl InputPin for Idle {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+}
+
+/// Delay that returns immediately
+pub(crate) struct NoDelay;
+
+impl DelayNs for NoDelay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
+/// Delay that records every wait in milliseconds
+#[derive(Clone, Default)]
+pub(crate) struct Delays(Rc<RefCell<Vec<u32>>>);
+
+impl Delays {
+    pub(crate) fn waits(&self) -> Vec<u32> {
+        self.0.borrow().clone()
+    }
+}
+
+impl DelayNs for Delays {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.borrow_mut().push(ns.div_ceil(1_000_000));
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.0.borrow_mut().push(ms);
+    }
+}
+
+/// Time passing 100 ms per clock read and while sleeping
+#[derive(Clone, Default)]
+pub(crate) struct Ticks(Rc<Cell<u64>>);
+
+impl Ticks {
+    pub(crate) fn get(&self) -> u64 {
+        self.0.get()
+    }
+}
+
+impl Clock for Ticks {
+    fn now_ms(&mut self) -> u64 {
+        self.0.set(self.0.get() + 100);
+        self.0.get()
+    }
+}
+
+impl DelayNs for Ticks {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.set(self.0.get() + u64::from(ns / 1_000_000));
+    }
+}
+
+/// BUSY pin following the simulated state
+pub(crate) struct Busy(Sim);
+
+impl PinErrorType for Busy {
+    type Error = Infallible;
+}
+
+impl InputPin for Busy {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok((self.0).0.borrow().busy)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(!(self.0).0.borrow().busy)
+    }
+}
+
+/// Simulated 23K256-style SPI SRAM
+#[derive(Clone)]
+pub(crate) struct SramChip(Rc<RefCell<SramState>>);
+
+struct SramState {
+    memory: Vec<u8>,
+    mode: u8,
+}
+
+impl SramChip {
+    pub(crate) fn new(len: usize) -> Self {
+        SramChip(Rc::new(RefCell::new(SramState {
+            memory: std::vec![0; len],
+            // byte mode after power up
+            mode: 0x01,
+        })))
+    }
+
+    pub(crate) fn memory(&self) -> Vec<u8> {
+        self.0.borrow().memory.clone()
+    }
+
+    pub(crate) fn mode(&self) -> u8 {
+        self.0.borrow().mode
+    }
+}
+
+impl SpiErrorType for SramChip {
+    type Error = Infallible;
+}
+
+impl SpiDevice for SramChip {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        let mut state = self.0.borrow_mut();
+        let len = state.memory.len();
+        // instruction and address bytes seen so far
+        let mut header = Vec::new();
+        let mut address = 0;
+        for op in operations {
+            match op {
+                Operation::Write(bytes) => {
+                    for &byte in bytes.iter() {
+                        match (header.first(), header.len()) {
+                            (Some(0x01), 1) => state.mode = byte,
+                            (Some(0x02), 3) => {
+                                state.memory[address % len] = byte;
+                                address += 1;
+                                continue;
+                            }
+                            (_, 3..) => continue,
+                            _ => {}
+                        }
+                        header.push(byte);
+                        if header.len() == 3 {
+                            address = usize::from(u16::from_be_bytes([header[1], header[2]]));
+                        }
+                    }
+                }
+                Operation::Read(buffer) => {
+                    let reading = header.len() == 3 && header[0] == 0x03;
+                    for byte in buffer.iter_mut() {
+                        *byte = if reading {
+                            state.memory[address % len]
+                        } else {
+                            0xFF
+                        };
+                        address += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,398 @@
+//! OTP display option, user ID and waveform programming
+
+use crate::cmd;
+use crate::controller;
+use crate::driver::{Ssd1681, LUT_LEN};
+use crate::interface::{DisplayError, ReadData, ReadError, WriteOnlyDataCommand};
+use crate::pin::{BusyPin, ResetPin};
+use crate::status::Fault;
+
+/// Number of bytes returned by the display option read (0x2D)
+pub const DISPLAY_OPTION_LEN: usize = 11;
+
+/// Number of bytes in the user ID (0x2E/0x38)
+pub const USER_ID_LEN: usize = 10;
+
+/// Opt-in token for writing registers that end up in OTP
+///
+/// Values written to these registers are burned into one-time programmable memory by the next
+/// OTP program command and can never be changed again. The only constructor has a name that
+/// stands out wherever the token is created.
+pub struct OtpUnlock {
+    _private: (),
+}
+
+impl OtpUnlock {
+    /// Create the token
+    ///
+    /// The caller confirms that the registers written with this token may be programmed into
+    /// OTP, permanently changing the panel.
+    pub fn i_understand_otp_is_irreversible() -> Self {
+        OtpUnlock { _private: () }
+    }
+}
+
+/// Display option as stored in OTP
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DisplayOption {
+    /// Use the spare VCOM OTP instead of the default one
+    pub spare_vcom: bool,
+    /// VCOM register value, read only
+    pub vcom: u8,
+    /// Display mode per waveform setting, bit `n` set selects display mode 2 for WS `n`
+    pub display_mode_2: u64,
+    /// Waveform version / module ID
+    pub waveform_version: u32,
+}
+
+impl DisplayOption {
+    /// Parses the response of the display option read
+    pub fn from_bytes(bytes: &[u8; DISPLAY_OPTION_LEN]) -> Self {
+        let mut display_mode_2 = 0;
+        for (i, &byte) in bytes[2..7].iter().enumerate() {
+            display_mode_2 |= u64::from(byte) << (8 * i);
+        }
+        DisplayOption {
+            spare_vcom: bytes[0] & 0x80 != 0,
+            vcom: bytes[1],
+            display_mode_2,
+            waveform_version: u32::from_be_bytes([bytes[7], bytes[8], bytes[9], bytes[10]]),
+        }
+    }
+
+    /// Data of the write register for display option command (0x37)
+    ///
+    /// The VCOM register is not part of it.
+    pub fn to_register_bytes(&self) -> [u8; DISPLAY_OPTION_LEN - 1] {
+        let mut bytes = [0; DISPLAY_OPTION_LEN - 1];
+        bytes[0] = if self.spare_vcom { 0x80 } else { 0x00 };
+        for (i, byte) in bytes[1..6].iter_mut().enumerate() {
+            *byte = (self.display_mode_2 >> (8 * i)) as u8;
+        }
+        bytes[6..].copy_from_slice(&self.waveform_version.to_be_bytes());
+        bytes
+    }
+
+    /// If waveform setting `ws` uses display mode 2
+    pub fn uses_display_mode_2(&self, ws: u8) -> bool {
+        ws < 40 && self.display_mode_2 & (1 << ws) != 0
+    }
+
+    /// If the option fits into the register, only waveform settings 0 to 39 exist
+    pub fn is_valid(&self) -> bool {
+        self.display_mode_2 >> 40 == 0
+    }
+}
+
+/// User ID stored in OTP
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UserId(pub [u8; USER_ID_LEN]);
+
+impl UserId {
+    /// The raw bytes
+    pub fn as_bytes(&self) -> &[u8; USER_ID_LEN] {
+        &self.0
+    }
+}
+
+/// Everything burned into OTP by [`OtpProgrammer`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OtpImage {
+    /// Waveform look up table
+    pub lut: [u8; LUT_LEN],
+    /// Display option
+    pub display_option: DisplayOption,
+    /// User ID
+    pub user_id: UserId,
+    /// OTP CRC of a panel programmed with this image, checked after programming
+    ///
+    /// The waveform can not be read back, the CRC is the only way to verify it.
+    pub expected_crc: u16,
+}
+
+/// Result of a successful programming run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProgramReport {
+    /// Nothing was written, neither to the registers nor to OTP
+    pub dry_run: bool,
+    /// The image that was, or in a dry run would be, programmed
+    pub image: OtpImage,
+    /// OTP CRC read back after loading the OTP, the expected CRC of the image in a dry run
+    pub crc: u16,
+}
+
+/// Error returned by [`OtpProgrammer`]
+#[derive(Debug)]
+pub enum ProgramError<E> {
+    /// SPI error
+    Spi(E),
+    /// The panel failed its self test, nothing was programmed
+    Fault(Fault),
+    /// The display option of the image does not fit into the register, nothing was programmed
+    InvalidDisplayOption,
+    /// The display option read back differs from the image
+    DisplayOptionMismatch(DisplayOption),
+    /// The user ID read back differs from the image
+    UserIdMismatch(UserId),
+    /// The OTP CRC differs from [`OtpImage::expected_crc`]
+    CrcMismatch {
+        /// CRC from the image
+        expected: u16,
+        /// CRC read back
+        actual: u16,
+    },
+}
+
+impl<E> From<E> for ProgramError<E> {
+    fn from(error: E) -> Self {
+        ProgramError::Spi(error)
+    }
+}
+
+impl From<DisplayError> for ProgramError<ReadError> {
+    fn from(error: DisplayError) -> Self {
+        ProgramError::Spi(error.into())
+    }
+}
+
+/// Burns a waveform, display option and user ID into OTP
+///
+/// Programming only starts if the panel passes its self test and the image is valid, and is
+/// followed by loading the OTP and comparing what it holds with the image. A
+/// [dry run](Self::dry_run) stops after these checks, which makes it safe to try against a mock
+/// SPI device or a panel that must stay untouched.
+pub struct OtpProgrammer<'a, DI, BUSY, RST, const SHADOW_ROWS: usize = 0> {
+    ssd1681: &'a mut Ssd1681<DI, BUSY, RST, controller::Ssd1681, SHADOW_ROWS>,
+}
+
+impl<'a, DI, BUSY, RST, const SHADOW_ROWS: usize> OtpProgrammer<'a, DI, BUSY, RST, SHADOW_ROWS>
+where
+    DI: WriteOnlyDataCommand + ReadData,
+    BUSY: BusyPin,
+    RST: ResetPin,
+{
+    /// Create a programmer for an initialised display driver
+    pub fn new(ssd1681: &'a mut Ssd1681<DI, BUSY, RST, controller::Ssd1681, SHADOW_ROWS>) -> Self {
+        OtpProgrammer { ssd1681 }
+    }
+
+    /// Program `image` into OTP, this can never be undone
+    ///
+    /// The controller needs an external programming voltage of 7.5 V on its VPP pin while the
+    /// OTP is written, which most modules do not break out. Without it the OTP is not, or only
+    /// partly, programmed. The clock and the analog block are kept on from writing the
+    /// registers until the OTP is loaded again.
+    pub fn program(
+        &mut self,
+        image: &OtpImage,
+        unlock: &OtpUnlock,
+    ) -> Result<ProgramReport, ProgramError<ReadError>> {
+        self.check(image)?;
+
+        self.ssd1681.with_clock_and_analog(|ssd1681| {
+            ssd1681.write_lut(&image.lut)?;
+            ssd1681.write_register_for_display_option(&image.display_option, unlock)?;
+            ssd1681.write_user_id(&image.user_id, unlock)?;
+            ssd1681.cmd_and_wait(cmd::PROGRAM_WS_OTP)?;
+            ssd1681.cmd_and_wait(cmd::PROGRAM_OTP_SELECTION)?;
+
+            ssd1681.cmd_and_wait(cmd::LOAD_WS_OTP)?;
+            Ok(())
+        })?;
+
+        let display_option = self.ssd1681.read_display_option()?;
+        let written = DisplayOption {
+            vcom: display_option.vcom,
+            ..image.display_option
+        };
+        if display_option != written {
+            return Err(ProgramError::DisplayOptionMismatch(display_option));
+        }
+
+        let user_id = self.ssd1681.read_user_id()?;
+        if user_id != image.user_id {
+            return Err(ProgramError::UserIdMismatch(user_id));
+        }
+
+        let crc = self.ssd1681.otp_crc()?;
+        if crc != image.expected_crc {
+            return Err(ProgramError::CrcMismatch {
+                expected: image.expected_crc,
+                actual: crc,
+            });
+        }
+
+        Ok(ProgramReport {
+            dry_run: false,
+            image: *image,
+            crc,
+        })
+    }
+
+    /// Run the checks of [`program`](Self::program) without writing anything
+    ///
+    /// Runs the self test and validates `image`. Neither the registers nor the OTP are
+    /// written, and what the OTP already holds is not looked at.
+    pub fn dry_run(&mut self, image: &OtpImage) -> Result<ProgramReport, ProgramError<ReadError>> {
+        self.check(image)?;
+        Ok(ProgramReport {
+            dry_run: true,
+            image: *image,
+            crc: image.expected_crc,
+        })
+    }
+
+    // Everything that has to pass before anything is written
+    fn check(&mut self, image: &OtpImage) -> Result<(), ProgramError<ReadError>> {
+        let diagnostic = self.ssd1681.self_test()?;
+        if let Some(fault) = diagnostic.fault() {
+            return Err(ProgramError::Fault(fault));
+        }
+        if !image.display_option.is_valid() {
+            return Err(ProgramError::InvalidDisplayOption);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flag;
+    use crate::mock::Sim;
+
+    fn image() -> OtpImage {
+        OtpImage {
+            lut: [0x22; LUT_LEN],
+            display_option: DisplayOption {
+                waveform_version: 7,
+                ..DisplayOption::default()
+            },
+            user_id: UserId(*b"batch-0001"),
+            expected_crc: 0xBEEF,
+        }
+    }
+
+    fn respond_programmed(sim: &Sim) {
+        sim.respond(cmd::STATUS_BIT_READ, &[0b01]);
+        sim.respond(
+            cmd::READ_DISPLAY_OPTION,
+            &[0, 0x40, 0, 0, 0, 0, 0, 0, 0, 0, 7],
+        );
+        sim.respond(cmd::READ_USER_ID, b"batch-0001");
+        sim.respond(cmd::CRC_STATUS_READ, &[0xBE, 0xEF]);
+    }
+
+    #[test]
+    fn program_verifies_read_back() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        respond_programmed(&sim);
+
+        let unlock = OtpUnlock::i_understand_otp_is_irreversible();
+        let report = OtpProgrammer::new(&mut ssd1681)
+            .program(&image(), &unlock)
+            .unwrap();
+
+        assert_eq!(report.crc, 0xBEEF);
+        assert!(!report.dry_run);
+        assert_eq!(sim.data_for(cmd::WRITE_LUT).unwrap(), [0x22; LUT_LEN]);
+        assert!(sim.data_for(cmd::PROGRAM_WS_OTP).is_some());
+        assert!(sim.data_for(cmd::PROGRAM_OTP_SELECTION).is_some());
+
+        // the clock and the analog block run from the LUT write until the OTP is loaded
+        let commands = sim.commands();
+        let position = |command: u8, data: &[u8]| {
+            commands
+                .iter()
+                .rposition(|(c, d)| *c == command && d.as_slice() == data)
+                .unwrap()
+        };
+        let enabled = position(cmd::UPDATE_DISPLAY_CTRL2, &[flag::ENABLE_CLOCK_ANALOG]);
+        let disabled = position(cmd::UPDATE_DISPLAY_CTRL2, &[flag::DISABLE_CLOCK_ANALOG]);
+        let program = position(cmd::PROGRAM_WS_OTP, &[]);
+        let load = position(cmd::LOAD_WS_OTP, &[]);
+        assert_eq!(commands[enabled + 1].0, cmd::MASTER_ACTIVATE);
+        assert!(enabled < position(cmd::WRITE_LUT, &[0x22; LUT_LEN]));
+        assert!(enabled < program && program < load && load < disabled);
+    }
+
+    #[test]
+    fn dry_run_never_writes() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        respond_programmed(&sim);
+        // an OTP holding something else does not matter
+        sim.respond(cmd::READ_USER_ID, b"batch-0000");
+
+        let report = OtpProgrammer::new(&mut ssd1681).dry_run(&image()).unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!(report.image, image());
+        assert_eq!(report.crc, 0xBEEF);
+        assert!(sim.data_for(cmd::STATUS_BIT_READ).is_some());
+        for command in [
+            cmd::WRITE_LUT,
+            cmd::WRITE_DISPLAY_OPTION,
+            cmd::WRITE_USER_ID,
+            cmd::PROGRAM_WS_OTP,
+            cmd::PROGRAM_OTP_SELECTION,
+            cmd::LOAD_WS_OTP,
+            cmd::READ_USER_ID,
+        ]
+        .iter()
+        {
+            assert!(sim.data_for(*command).is_none());
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_image() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        respond_programmed(&sim);
+
+        let mut image = image();
+        image.display_option.display_mode_2 = 1 << 40;
+        let result = OtpProgrammer::new(&mut ssd1681).dry_run(&image);
+
+        assert!(matches!(result, Err(ProgramError::InvalidDisplayOption)));
+    }
+
+    #[test]
+    fn refuses_to_program_faulty_panel() {
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        respond_programmed(&sim);
+        // VCI below the detection level
+        sim.respond(cmd::STATUS_BIT_READ, &[0b01_0001]);
+
+        let unlock = OtpUnlock::i_understand_otp_is_irreversible();
+        let result = OtpProgrammer::new(&mut ssd1681).program(&image(), &unlock);
+
+        assert!(matches!(
+            result,
+            Err(ProgramError::Fault(Fault::LowVoltage))
+        ));
+        assert!(sim.data_for(cmd::WRITE_LUT).is_none());
+    }
+
+    #[test]
+    fn display_option_round_trip() {
+        let bytes = [
+            0x80, 0x50, 0x01, 0x00, 0x00, 0x00, 0x80, 0x12, 0x34, 0x56, 0x78,
+        ];
+        let option = DisplayOption::from_bytes(&bytes);
+
+        assert!(option.spare_vcom);
+        assert_eq!(option.vcom, 0x50);
+        assert!(option.uses_display_mode_2(0));
+        assert!(!option.uses_display_mode_2(1));
+        assert!(option.uses_display_mode_2(39));
+        assert_eq!(option.waveform_version, 0x1234_5678);
+        assert_eq!(
+            option.to_register_bytes(),
+            [0x80, 0x01, 0x00, 0x00, 0x00, 0x80, 0x12, 0x34, 0x56, 0x78]
+        );
+    }
+}
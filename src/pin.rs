@@ -0,0 +1,166 @@
+//! BUSY and RST pins, and stand-ins for modules that do not break them out
+//!
+//! Any [`InputPin`] is a [`BusyPin`] and any [`OutputPin`] a [`ResetPin`]. Without a BUSY pin
+//! use [`NoBusy`], it waits the worst case time of whatever the controller was asked to do.
+//! Without a RST pin use [`NoPin`], the controller is then only reset with the software reset
+//! command.
+
+use core::fmt::Debug;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+
+/// Time the reset pin is held low, and waited for after it is released
+const RESET_DELAY_MS: u32 = 10;
+
+/// What the controller is busy with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusyWith {
+    /// Software reset
+    Reset,
+    /// A command like loading the OTP or a detection
+    Command,
+    /// Full refresh (display mode 1)
+    FullRefresh,
+    /// Partial refresh (display mode 2)
+    PartialRefresh,
+}
+
+impl BusyWith {
+    /// Longest time the operation takes, with some margin
+    ///
+    /// The full refresh of a tri-color panel takes the longest, about 15 s in the cold.
+    pub fn worst_case_ms(self) -> u32 {
+        match self {
+            BusyWith::Reset => 20,
+            BusyWith::Command => 500,
+            BusyWith::FullRefresh => 20_000,
+            BusyWith::PartialRefresh => 2_000,
+        }
+    }
+}
+
+/// BUSY pin of the controller
+pub trait BusyPin {
+    /// Note that the controller was just asked to do `operation`
+    fn start(&mut self, operation: BusyWith);
+
+    /// Check if the controller is busy
+    fn is_busy(&mut self) -> bool;
+
+    /// Wait until the controller is idle
+    fn wait_until_idle(&mut self);
+}
+
+impl<P: InputPin> BusyPin for P {
+    fn start(&mut self, _operation: BusyWith) {}
+
+    fn is_busy(&mut self) -> bool {
+        // high for busy, a pin that cannot be read is treated as busy
+        self.is_high().unwrap_or(true)
+    }
+
+    fn wait_until_idle(&mut self) {
+        while self.is_busy() {}
+    }
+}
+
+/// Stand-in for a BUSY pin that is not connected
+///
+/// Every wait takes the [worst case time](BusyWith::worst_case_ms) of the last operation.
+/// Since there is no way to tell whether the controller is done, [`is_busy`](BusyPin::is_busy)
+/// waits too and then reports idle. Polling a refresh blocks for up to 20 s this way.
+pub struct NoBusy<DELAY> {
+    delay: DELAY,
+    pending: Option<BusyWith>,
+}
+
+impl<DELAY: DelayNs> NoBusy<DELAY> {
+    /// Wait with `delay`
+    pub fn new(delay: DELAY) -> Self {
+        NoBusy {
+            delay,
+            pending: None,
+        }
+    }
+}
+
+impl<DELAY: DelayNs> BusyPin for NoBusy<DELAY> {
+    fn start(&mut self, operation: BusyWith) {
+        // operations started back to back are waited for together
+        self.pending = match self.pending {
+            Some(pending) if pending.worst_case_ms() > operation.worst_case_ms() => Some(pending),
+            _ => Some(operation),
+        };
+    }
+
+    fn is_busy(&mut self) -> bool {
+        self.wait_until_idle();
+        false
+    }
+
+    fn wait_until_idle(&mut self) {
+        if let Some(operation) = self.pending.take() {
+            self.delay.delay_ms(operation.worst_case_ms());
+        }
+    }
+}
+
+/// RST pin of the controller
+pub trait ResetPin {
+    /// If [`reset`](Self::reset) is a hardware reset, the only way out of deep sleep
+    const CAN_RESET: bool = true;
+
+    /// Reset the controller
+    fn reset<DELAY: DelayNs>(&mut self, delay: &mut DELAY);
+}
+
+impl<P> ResetPin for P
+where
+    P: OutputPin,
+    P::Error: Debug,
+{
+    fn reset<DELAY: DelayNs>(&mut self, delay: &mut DELAY) {
+        self.set_low().unwrap();
+        delay.delay_ms(RESET_DELAY_MS);
+        self.set_high().unwrap();
+        delay.delay_ms(RESET_DELAY_MS);
+    }
+}
+
+/// Stand-in for a RST pin that is not connected
+///
+/// Only a hardware reset brings the controller out of deep sleep, so without the pin the
+/// panel has to be power cycled after [`sleep`](crate::driver::Ssd1681::sleep).
+/// [`wake_up`](crate::driver::Ssd1681::wake_up) fails with [`DisplayError::RSError`].
+///
+/// [`DisplayError::RSError`]: crate::interface::DisplayError::RSError
+pub struct NoPin;
+
+impl ResetPin for NoPin {
+    const CAN_RESET: bool = false;
+
+    fn reset<DELAY: DelayNs>(&mut self, _delay: &mut DELAY) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BusyPin, BusyWith, NoBusy};
+    use crate::mock::Delays;
+
+    #[test]
+    fn no_busy_waits_worst_case() {
+        let delays = Delays::default();
+        let mut busy = NoBusy::new(delays.clone());
+
+        busy.wait_until_idle();
+        busy.start(BusyWith::FullRefresh);
+        busy.start(BusyWith::Command);
+        assert!(!busy.is_busy());
+        busy.wait_until_idle();
+        busy.start(BusyWith::PartialRefresh);
+        busy.wait_until_idle();
+
+        assert_eq!(delays.waits(), [20_000, 2_000]);
+    }
+}
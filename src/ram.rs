@@ -0,0 +1,195 @@
+//! Reading back, verifying and shadowing the controller RAM
+
+use crate::cmd;
+use crate::controller::PanelSize;
+use crate::interface::{DisplayError, ReadError};
+
+/// One of the two RAM planes of the controller
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RamPlane {
+    /// The black and white RAM (0x24)
+    BlackWhite,
+    /// The red RAM (0x26)
+    Red,
+}
+
+impl RamPlane {
+    pub(crate) fn read_option(self) -> u8 {
+        match self {
+            RamPlane::BlackWhite => 0x00,
+            RamPlane::Red => 0x01,
+        }
+    }
+
+    pub(crate) fn write_command(self) -> u8 {
+        match self {
+            RamPlane::BlackWhite => cmd::WRITE_BW_DATA,
+            RamPlane::Red => cmd::WRITE_RED_DATA,
+        }
+    }
+
+    pub(crate) fn auto_write_command(self) -> u8 {
+        match self {
+            RamPlane::BlackWhite => cmd::AUTO_WRITE_BW,
+            RamPlane::Red => cmd::AUTO_WRITE_RED,
+        }
+    }
+}
+
+/// What has to be sent to bring a RAM plane up to date
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Upload {
+    /// The RAM already holds the buffer
+    Skip,
+    /// Only the rows from `first` to `last` changed
+    Rows(usize, usize),
+    /// The whole buffer has to be sent
+    All,
+}
+
+/// Hash of every row last written to a RAM plane
+///
+/// Rows are compared by their [`crc16`], so a changed row with the same CRC as before is
+/// skipped. Changes of up to three bits, or within 16 consecutive bits, are always detected.
+///
+/// With `ROWS` 0 nothing is tracked and every update sends the whole buffer.
+pub(crate) struct RamShadow<const ROWS: usize> {
+    rows: [u16; ROWS],
+    valid: bool,
+}
+
+impl<const ROWS: usize> RamShadow<ROWS> {
+    pub(crate) fn new() -> Self {
+        RamShadow {
+            rows: [0; ROWS],
+            valid: false,
+        }
+    }
+
+    /// Forget what the RAM holds
+    pub(crate) fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    /// Record that every byte of the RAM of a panel of `size` is `value`
+    pub(crate) fn fill(&mut self, size: PanelSize, value: u8) {
+        if ROWS == 0 {
+            return;
+        }
+        let mut crc = 0xFFFF;
+        for _ in 0..size.row_len() {
+            crc = crc16_update(crc, &[value]);
+        }
+        self.rows = [crc; ROWS];
+        self.valid = true;
+    }
+
+    /// Record that `buffer` is about to be written and return what has to be sent for it
+    pub(crate) fn update(&mut self, size: PanelSize, buffer: &[u8]) -> Upload {
+        if ROWS == 0 {
+            return Upload::All;
+        }
+        let row_len = size.row_len();
+        if buffer.len() != row_len * usize::from(size.height) {
+            // only whole planes can be tracked
+            self.valid = false;
+            return Upload::All;
+        }
+
+        let mut changed: Option<(usize, usize)> = None;
+        for (i, row) in buffer.chunks(row_len).enumerate() {
+            let hash = crc16(row);
+            if hash != self.rows[i] {
+                self.rows[i] = hash;
+                changed = Some(changed.map_or((i, i), |(first, _)| (first, i)));
+            }
+        }
+
+        if !self.valid {
+            self.valid = true;
+            return Upload::All;
+        }
+        match changed {
+            None => Upload::Skip,
+            Some((first, last)) => Upload::Rows(first, last),
+        }
+    }
+
+    /// Record that the rows `first` to `last` of `buffer` were written
+    pub(crate) fn update_rows(
+        &mut self,
+        size: PanelSize,
+        buffer: &[u8],
+        first: usize,
+        last: usize,
+    ) {
+        if ROWS == 0 {
+            return;
+        }
+        let row_len = size.row_len();
+        if buffer.len() != row_len * usize::from(size.height) {
+            self.valid = false;
+            return;
+        }
+        for i in first..=last {
+            self.rows[i] = crc16(&buffer[i * row_len..(i + 1) * row_len]);
+        }
+    }
+}
+
+/// Error returned by [`Ssd1681::verify_frame`](crate::driver::Ssd1681::verify_frame)
+#[derive(Debug)]
+pub enum VerifyError<E> {
+    /// SPI error while reading back the RAM
+    Spi(E),
+    /// The RAM content does not match the buffer
+    Mismatch {
+        /// CRC of the buffer
+        expected: u16,
+        /// CRC of the RAM content
+        actual: u16,
+    },
+}
+
+impl<E> From<E> for VerifyError<E> {
+    fn from(error: E) -> Self {
+        VerifyError::Spi(error)
+    }
+}
+
+impl From<DisplayError> for VerifyError<ReadError> {
+    fn from(error: DisplayError) -> Self {
+        VerifyError::Spi(error.into())
+    }
+}
+
+/// CRC-16/CCITT-FALSE (polynomial 0x1021, initial value 0xFFFF)
+pub fn crc16(data: &[u8]) -> u16 {
+    crc16_update(0xFFFF, data)
+}
+
+/// Continue a [`crc16`] over more data
+pub fn crc16_update(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16_update(crc16(b"1234"), b"56789"), 0x29B1);
+    }
+}
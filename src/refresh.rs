@@ -0,0 +1,226 @@
+//! Limits on how often and how the panel is refreshed
+//!
+//! Panel vendors ask for a full refresh every few partial refreshes to clear ghosting, and
+//! for a minimum interval between refreshes (180 s for tri-color panels). A [`RefreshPolicy`]
+//! set with [`Ssd1681::set_refresh_policy`](crate::driver::Ssd1681::set_refresh_policy) is
+//! enforced by [`Ssd1681::refresh`](crate::driver::Ssd1681::refresh).
+//!
+//! Every refresh counts towards the policy, also the ones started without a clock like
+//! [`Ssd1681::display_frame`](crate::driver::Ssd1681::display_frame). Since their time is not
+//! known they count as done when `refresh` next reads the clock.
+
+/// Monotonic time source in milliseconds
+///
+/// The value only has to grow, the starting point does not matter.
+pub trait Clock {
+    /// Milliseconds since some fixed point in the past
+    fn now_ms(&mut self) -> u64;
+}
+
+/// Waveform to refresh the panel with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefreshKind {
+    /// Full refresh (display mode 1)
+    Full,
+    /// Partial refresh (display mode 2)
+    Partial,
+}
+
+/// What to do with a refresh that comes before [`RefreshPolicy::min_interval_ms`] has passed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TooSoon {
+    /// Return [`RefreshError::TooSoon`]
+    #[default]
+    Reject,
+    /// Sleep until the refresh is allowed
+    Defer,
+}
+
+/// Rules for refreshing the panel
+///
+/// The default has no limits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RefreshPolicy {
+    /// Turn a partial refresh into a full one after this many partial refreshes in a row
+    pub full_every: Option<u16>,
+    /// Turn a partial refresh into a full one when the last full refresh is older than this
+    pub full_after_ms: Option<u64>,
+    /// Minimum time between the start of two refreshes
+    pub min_interval_ms: u64,
+    /// What to do when a refresh comes too soon
+    pub too_soon: TooSoon,
+}
+
+impl RefreshPolicy {
+    /// At most one refresh every 180 seconds, as tri-color panels require
+    pub const TRI_COLOR: RefreshPolicy = RefreshPolicy {
+        full_every: None,
+        full_after_ms: None,
+        min_interval_ms: 180_000,
+        too_soon: TooSoon::Reject,
+    };
+}
+
+/// Error returned by [`Ssd1681::refresh`](crate::driver::Ssd1681::refresh)
+#[derive(Debug)]
+pub enum RefreshError<E> {
+    /// SPI error while starting the refresh
+    Spi(E),
+    /// The last refresh was too recent, try again later
+    TooSoon {
+        /// Time until the refresh is allowed
+        remaining_ms: u64,
+    },
+}
+
+impl<E> From<E> for RefreshError<E> {
+    fn from(error: E) -> Self {
+        RefreshError::Spi(error)
+    }
+}
+
+/// Refreshes done so far
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct RefreshHistory {
+    partials: u16,
+    last_full_ms: Option<u64>,
+    last_refresh_ms: Option<u64>,
+    // refreshes recorded without a time since the last stamp
+    unstamped: Option<RefreshKind>,
+}
+
+impl RefreshHistory {
+    /// Give the refreshes recorded without a time the time `now_ms`
+    pub(crate) fn stamp(&mut self, now_ms: u64) {
+        if let Some(kind) = self.unstamped.take() {
+            if kind == RefreshKind::Full {
+                self.last_full_ms = Some(now_ms);
+            }
+            self.last_refresh_ms = Some(now_ms);
+        }
+    }
+
+    /// Time from `now_ms` until a refresh is allowed, 0 if it is allowed now
+    pub(crate) fn wait_ms(&self, policy: &RefreshPolicy, now_ms: u64) -> u64 {
+        match self.last_refresh_ms {
+            Some(last) => (last + policy.min_interval_ms).saturating_sub(now_ms),
+            None => 0,
+        }
+    }
+
+    /// The refresh to do at `now_ms` when `requested` was asked for
+    pub(crate) fn kind(
+        &self,
+        policy: &RefreshPolicy,
+        requested: RefreshKind,
+        now_ms: u64,
+    ) -> RefreshKind {
+        if requested == RefreshKind::Full {
+            return RefreshKind::Full;
+        }
+        let too_many = policy.full_every.is_some_and(|n| self.partials >= n);
+        let too_old = policy
+            .full_after_ms
+            .is_some_and(|span| match self.last_full_ms {
+                Some(last) => now_ms.saturating_sub(last) >= span,
+                // the panel content is unknown
+                None => true,
+            });
+        if too_many || too_old {
+            RefreshKind::Full
+        } else {
+            RefreshKind::Partial
+        }
+    }
+
+    pub(crate) fn record(&mut self, kind: RefreshKind, now_ms: u64) {
+        self.count(kind);
+        if kind == RefreshKind::Full {
+            self.last_full_ms = Some(now_ms);
+        }
+        self.last_refresh_ms = Some(now_ms);
+    }
+
+    /// Record a refresh at a time that is not known
+    pub(crate) fn record_unstamped(&mut self, kind: RefreshKind) {
+        self.count(kind);
+        if self.unstamped != Some(RefreshKind::Full) {
+            self.unstamped = Some(kind);
+        }
+    }
+
+    fn count(&mut self, kind: RefreshKind) {
+        match kind {
+            RefreshKind::Full => self.partials = 0,
+            RefreshKind::Partial => self.partials = self.partials.saturating_add(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forces_full_and_spaces_refreshes() {
+        let policy = RefreshPolicy {
+            full_every: Some(2),
+            full_after_ms: Some(10_000),
+            min_interval_ms: 1_000,
+            too_soon: TooSoon::Reject,
+        };
+        let mut history = RefreshHistory::default();
+
+        // nothing is known about the panel yet
+        assert_eq!(history.wait_ms(&policy, 0), 0);
+        assert_eq!(
+            history.kind(&policy, RefreshKind::Partial, 0),
+            RefreshKind::Full
+        );
+        history.record(RefreshKind::Full, 0);
+
+        assert_eq!(history.wait_ms(&policy, 400), 600);
+        for now in [1_000, 2_000] {
+            assert_eq!(
+                history.kind(&policy, RefreshKind::Partial, now),
+                RefreshKind::Partial
+            );
+            history.record(RefreshKind::Partial, now);
+        }
+        assert_eq!(
+            history.kind(&policy, RefreshKind::Partial, 3_000),
+            RefreshKind::Full
+        );
+
+        history.record(RefreshKind::Full, 3_000);
+        assert_eq!(
+            history.kind(&policy, RefreshKind::Partial, 13_000),
+            RefreshKind::Full
+        );
+    }
+
+    #[test]
+    fn unstamped_refreshes_count_when_seen() {
+        let policy = RefreshPolicy {
+            full_every: Some(1),
+            full_after_ms: Some(10_000),
+            min_interval_ms: 1_000,
+            too_soon: TooSoon::Reject,
+        };
+        let mut history = RefreshHistory::default();
+        history.record(RefreshKind::Partial, 0);
+
+        history.record_unstamped(RefreshKind::Full);
+        history.record_unstamped(RefreshKind::Partial);
+        assert_eq!(history.wait_ms(&policy, 5_000), 0);
+        history.stamp(5_000);
+
+        assert_eq!(history.wait_ms(&policy, 5_000), 1_000);
+        // one partial since the full refresh, which is not too old
+        assert_eq!(
+            history.kind(&policy, RefreshKind::Partial, 14_000),
+            RefreshKind::Full
+        );
+        assert_eq!(history.last_full_ms, Some(5_000));
+    }
+}
@@ -0,0 +1,410 @@
+//! High level screen owning the driver and the buffers
+
+use core::convert::Infallible;
+use core::fmt::Debug;
+
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::spi::SpiDevice;
+
+use crate::color::{Color, TriColor};
+use crate::controller;
+use crate::driver::{DeepSleepMode, Ssd1681};
+use crate::graphics::{Display, Display1in54, DisplayRotation};
+use crate::interface::{DisplayError, SpiInterface, WriteOnlyDataCommand};
+use crate::pin::{BusyPin, ResetPin};
+use crate::refresh::{Clock, RefreshError, RefreshKind};
+use crate::typestate::TransitionError;
+
+/// Time between two looks at the BUSY pin while [`Screen::flush`] waits
+const BUSY_POLL_MS: u32 = 1;
+
+/// Error returned by [`Screen`]
+#[derive(Debug)]
+pub enum ScreenError {
+    /// Error of the transport to the controller
+    Display(DisplayError),
+    /// The controller is in deep sleep, call [`Screen::wake_up`] first
+    Asleep,
+    /// The [`RefreshPolicy`](crate::refresh::RefreshPolicy) does not allow a refresh yet
+    ///
+    /// The planes were sent, the next flush shows them.
+    TooSoon {
+        /// Time until the refresh is allowed
+        remaining_ms: u64,
+    },
+}
+
+impl From<DisplayError> for ScreenError {
+    fn from(error: DisplayError) -> Self {
+        ScreenError::Display(error)
+    }
+}
+
+impl From<RefreshError<DisplayError>> for ScreenError {
+    fn from(error: RefreshError<DisplayError>) -> Self {
+        match error {
+            RefreshError::Spi(error) => ScreenError::Display(error),
+            RefreshError::TooSoon { remaining_ms } => ScreenError::TooSoon { remaining_ms },
+        }
+    }
+}
+
+/// A tri-color screen
+///
+/// Draw on it with [`embedded_graphics`] and call [`flush`](Self::flush) to show the result.
+/// Only the area of each plane that was drawn on since the last flush is sent to the controller.
+/// Flushes refresh the panel following the
+/// [`RefreshPolicy`](crate::refresh::RefreshPolicy) of the driver.
+///
+/// `SHADOW_ROWS` is that of a driver created with
+/// [`Ssd1681::with_shadow`](crate::driver::Ssd1681::with_shadow).
+pub struct Screen<DI, BUSY, RST, const SHADOW_ROWS: usize = 0> {
+    ssd1681: Ssd1681<DI, BUSY, RST, controller::Ssd1681, SHADOW_ROWS>,
+    bw: Display1in54,
+    red: Display1in54,
+    asleep: bool,
+}
+
+impl<SPI, DC, BUSY, RST> Screen<SpiInterface<SPI, DC>, BUSY, RST>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+    DC::Error: Debug,
+    BUSY: BusyPin,
+    RST: ResetPin,
+{
+    /// Create the screen on a 4-wire SPI bus and initialise the controller
+    pub fn new<DELAY: DelayNs>(
+        spi: SPI,
+        busy: BUSY,
+        dc: DC,
+        rst: RST,
+        delay: &mut DELAY,
+    ) -> Result<Self, DisplayError> {
+        let ssd1681 = Ssd1681::new(spi, busy, dc, rst, delay)?;
+        Ok(Screen::from_driver(ssd1681))
+    }
+}
+
+impl<DI, BUSY, RST, const SHADOW_ROWS: usize> Screen<DI, BUSY, RST, SHADOW_ROWS>
+where
+    DI: WriteOnlyDataCommand,
+    BUSY: BusyPin,
+    RST: ResetPin,
+{
+    /// Create the screen around an initialised driver
+    ///
+    /// The buffers start out dirty, so the first flush sends both planes whole.
+    pub fn from_driver(ssd1681: Ssd1681<DI, BUSY, RST, controller::Ssd1681, SHADOW_ROWS>) -> Self {
+        Screen {
+            ssd1681,
+            bw: Display1in54::bw(),
+            red: Display1in54::red(),
+            asleep: false,
+        }
+    }
+
+    /// Send the changed planes and update the whole display
+    ///
+    /// The [`RefreshPolicy`](crate::refresh::RefreshPolicy) is not looked at, the update
+    /// starts right away. Waiting for it to finish sleeps on `delay`.
+    pub fn flush<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), ScreenError> {
+        self.send_dirty_planes()?;
+        self.ssd1681.start_refresh()?;
+        while self.ssd1681.is_busy() {
+            delay.delay_ms(BUSY_POLL_MS);
+        }
+        Ok(())
+    }
+
+    /// Send the changed planes and update the whole display following the refresh policy
+    ///
+    /// See [`Ssd1681::refresh`] for `clock` and `delay`.
+    pub fn flush_with_policy<CLOCK: Clock, DELAY: DelayNs>(
+        &mut self,
+        clock: &mut CLOCK,
+        delay: &mut DELAY,
+    ) -> Result<(), ScreenError> {
+        self.send_dirty_planes()?;
+        self.ssd1681.refresh(clock, delay, RefreshKind::Full)?;
+        Ok(())
+    }
+
+    /// Send the changed planes and update the display with the partial update waveform
+    ///
+    /// The policy can turn it into a full update, the update that was done is returned. See
+    /// [`Ssd1681::display_partial_frame`].
+    ///
+    /// The partial waveform of black and white panels compares against the previous image in
+    /// the red RAM, which the screen uses for the red plane instead. Partial flushes are
+    /// therefore only clean on panels whose waveform does not look at the red RAM, use
+    /// [`flush`](Self::flush) on the others.
+    pub fn flush_partial<CLOCK: Clock, DELAY: DelayNs>(
+        &mut self,
+        clock: &mut CLOCK,
+        delay: &mut DELAY,
+    ) -> Result<RefreshKind, ScreenError> {
+        self.send_dirty_planes()?;
+        Ok(self.ssd1681.refresh(clock, delay, RefreshKind::Partial)?)
+    }
+
+    /// Make the buffers and the controller RAM white
+    ///
+    /// The controller RAM is filled directly, so the next flush only has to refresh.
+    pub fn clear_all(&mut self) -> Result<(), ScreenError> {
+        self.check_awake()?;
+        // the same bytes the driver fills the RAM with
+        self.bw.get_mut_buffer().fill(Color::White.get_byte_value());
+        self.red
+            .get_mut_buffer()
+            .fill(Color::White.inverse().get_byte_value());
+        self.ssd1681.clear_bw_frame()?;
+        self.ssd1681.clear_red_frame()?;
+        self.bw.take_dirty();
+        self.red.take_dirty();
+        Ok(())
+    }
+
+    /// Put the controller into deep sleep, keeping its RAM
+    ///
+    /// Flushing fails with [`ScreenError::Asleep`] until [`wake_up`](Self::wake_up), drawing
+    /// into the buffers goes on as usual.
+    pub fn sleep(&mut self) -> Result<(), DisplayError> {
+        self.ssd1681.sleep(DeepSleepMode::RetainRam)?;
+        self.asleep = true;
+        Ok(())
+    }
+
+    /// Wake the controller up from deep sleep
+    pub fn wake_up<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        self.ssd1681.wake_up(delay)?;
+        self.asleep = false;
+        Ok(())
+    }
+
+    /// If the controller is in deep sleep
+    pub fn is_asleep(&self) -> bool {
+        self.asleep
+    }
+
+    /// Sets the rotation of both buffers
+    pub fn set_rotation(&mut self, rotation: DisplayRotation) {
+        self.bw.set_rotation(rotation);
+        self.red.set_rotation(rotation);
+    }
+
+    /// Get the current rotation
+    pub fn rotation(&self) -> DisplayRotation {
+        self.bw.rotation()
+    }
+
+    /// The black and white buffer
+    pub fn bw(&self) -> &Display1in54 {
+        &self.bw
+    }
+
+    /// The black and white buffer
+    ///
+    /// Drawing on it is sent on the next flush, direct writes to the buffer have to be marked
+    /// with [`Display::mark_dirty`].
+    pub fn bw_mut(&mut self) -> &mut Display1in54 {
+        &mut self.bw
+    }
+
+    /// The red buffer
+    pub fn red(&self) -> &Display1in54 {
+        &self.red
+    }
+
+    /// The red buffer
+    ///
+    /// Like [`bw_mut`](Self::bw_mut) only drawing is tracked.
+    pub fn red_mut(&mut self) -> &mut Display1in54 {
+        &mut self.red
+    }
+
+    /// The display driver, for everything `Screen` does not cover
+    pub fn driver(&mut self) -> &mut Ssd1681<DI, BUSY, RST, controller::Ssd1681, SHADOW_ROWS> {
+        &mut self.ssd1681
+    }
+
+    /// Drop the buffers and return the transport and the pins
+    ///
+    /// The controller is left as it is, see [`release_sleeping`](Self::release_sleeping).
+    pub fn release(self) -> (DI, BUSY, RST) {
+        self.ssd1681.release()
+    }
+
+    /// Put the controller into deep sleep unless it already is, then drop the buffers and
+    /// return the transport and the pins
+    #[allow(clippy::result_large_err)]
+    pub fn release_sleeping(
+        mut self,
+        mode: DeepSleepMode,
+    ) -> Result<(DI, BUSY, RST), TransitionError<Self>> {
+        if !self.asleep {
+            if let Err(error) = self.ssd1681.sleep(mode) {
+                return Err(TransitionError {
+                    driver: self,
+                    error,
+                });
+            }
+        }
+        Ok(self.release())
+    }
+
+    fn check_awake(&self) -> Result<(), ScreenError> {
+        if self.asleep {
+            Err(ScreenError::Asleep)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn send_dirty_planes(&mut self) -> Result<(), ScreenError> {
+        self.check_awake()?;
+        if let Some(area) = self.bw.dirty_area() {
+            self.ssd1681.update_bw_area(self.bw.buffer(), area.into())?;
+            self.bw.take_dirty();
+        }
+        if let Some(area) = self.red.dirty_area() {
+            self.ssd1681
+                .update_red_area(self.red.buffer(), area.into())?;
+            self.red.take_dirty();
+        }
+        Ok(())
+    }
+}
+
+impl<DI, BUSY, RST, const SHADOW_ROWS: usize> Dimensions for Screen<DI, BUSY, RST, SHADOW_ROWS> {
+    fn bounding_box(&self) -> Rectangle {
+        self.bw.bounding_box()
+    }
+}
+
+impl<DI, BUSY, RST, const SHADOW_ROWS: usize> DrawTarget for Screen<DI, BUSY, RST, SHADOW_ROWS> {
+    type Color = TriColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels.into_iter() {
+            // the buffers draw black and red with `On`
+            let (bw, red) = color.plane_bits();
+            if let Some(white) = bw {
+                Pixel(point, BinaryColor::from(!white)).draw(&mut self.bw)?;
+            }
+            Pixel(point, BinaryColor::from(red)).draw(&mut self.red)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Screen, ScreenError};
+    use crate::cmd;
+    use crate::color::TriColor;
+    use crate::graphics::Display;
+    use crate::interface::SpiInterface;
+    use crate::mock::{Dc, Idle, NoDelay, Pin, Sim, Spi, Ticks};
+    use crate::refresh::RefreshPolicy;
+    use embedded_graphics::{prelude::*, primitives::PrimitiveStyle, primitives::Rectangle};
+
+    fn fill(screen: &mut Screen<SpiInterface<Spi, Dc>, Idle, Pin>, color: TriColor) {
+        Rectangle::new(Point::new(0, 0), Size::new(8, 1))
+            .into_styled(PrimitiveStyle::with_fill(color))
+            .draw(screen)
+            .unwrap();
+    }
+
+    #[test]
+    fn flush_sends_changed_planes() {
+        let sim = Sim::new();
+        let mut screen = sim.screen();
+
+        fill(&mut screen, TriColor::Black);
+        screen.flush(&mut NoDelay).unwrap();
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap()[0], 0x00);
+        assert_eq!(sim.data_for(cmd::WRITE_RED_DATA).unwrap()[0], 0x00);
+        assert_eq!(sim.data_for(cmd::UPDATE_DISPLAY_CTRL2).unwrap(), [0xF7]);
+
+        sim.clear();
+        fill(&mut screen, TriColor::Red);
+        screen
+            .flush_partial(&mut Ticks::default(), &mut NoDelay)
+            .unwrap();
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA), None);
+        assert_eq!(sim.data_for(cmd::SET_RAMXPOS).unwrap(), [0, 0]);
+        assert_eq!(sim.data_for(cmd::SET_RAMYPOS).unwrap(), [0, 0, 0, 0]);
+        assert_eq!(sim.data_for(cmd::WRITE_RED_DATA).unwrap(), [0xFF]);
+        assert_eq!(sim.data_for(cmd::UPDATE_DISPLAY_CTRL2).unwrap(), [0xFF]);
+    }
+
+    #[test]
+    fn clear_fills_ram_directly() {
+        let sim = Sim::new();
+        let mut screen = sim.screen();
+        fill(&mut screen, TriColor::Black);
+
+        screen.clear_all().unwrap();
+        assert!(screen.bw().buffer().iter().all(|&b| b == 0xFF));
+
+        sim.clear();
+        screen.flush(&mut NoDelay).unwrap();
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA), None);
+        assert_eq!(sim.data_for(cmd::WRITE_RED_DATA), None);
+        assert!(sim.data_for(cmd::MASTER_ACTIVATE).is_some());
+    }
+
+    #[test]
+    fn no_flush_while_asleep() {
+        let sim = Sim::new();
+        let mut screen = sim.screen();
+
+        screen.sleep().unwrap();
+        fill(&mut screen, TriColor::Black);
+        sim.clear();
+        assert!(matches!(
+            screen.flush(&mut NoDelay),
+            Err(ScreenError::Asleep)
+        ));
+        assert!(matches!(screen.clear_all(), Err(ScreenError::Asleep)));
+        assert_eq!(sim.transactions(), 0);
+
+        screen.wake_up(&mut NoDelay).unwrap();
+        screen.flush(&mut NoDelay).unwrap();
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap()[0], 0x00);
+    }
+
+    #[test]
+    fn flush_follows_policy() {
+        let sim = Sim::new();
+        let mut screen = sim.screen();
+        screen.driver().set_refresh_policy(RefreshPolicy {
+            min_interval_ms: 1_000,
+            ..RefreshPolicy::default()
+        });
+        let mut clock = Ticks::default();
+
+        screen.flush_with_policy(&mut clock, &mut NoDelay).unwrap();
+        fill(&mut screen, TriColor::Black);
+        sim.clear();
+        assert!(matches!(
+            screen.flush_with_policy(&mut clock, &mut NoDelay),
+            Err(ScreenError::TooSoon { remaining_ms: 900 })
+        ));
+        assert!(sim.data_for(cmd::WRITE_BW_DATA).is_some());
+        assert_eq!(sim.data_for(cmd::MASTER_ACTIVATE), None);
+
+        // the plain flush does not look at the policy
+        screen.flush(&mut NoDelay).unwrap();
+        assert!(sim.data_for(cmd::MASTER_ACTIVATE).is_some());
+    }
+}
@@ -0,0 +1,432 @@
+//! Frame buffer in an external SPI SRAM
+//!
+//! Two 5000 byte planes do not fit into the RAM of small MCUs. The Adafruit 1.54" breakout
+//! carries a 23K256 SPI SRAM for them, [`SramDisplay`] draws into it and
+//! [`upload`](SramDisplay::upload) streams the planes to the controller a chunk at a time.
+//!
+//! The SRAM sits on the same bus as the controller, give each its own [`SpiDevice`] with
+//! its own CS pin, for example with `embedded-hal-bus`.
+//!
+//! [`SramDisplay`] does not implement [`Display`](crate::graphics::Display). That trait hands
+//! out the frame as a `&[u8]`, which does not exist in MCU RAM here, and its methods can not
+//! report the SPI errors of the SRAM. Drawing goes through [`DrawTarget`] instead, and
+//! rotation and mirroring are inherent methods with the same meaning as on `Display`.
+
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_hal::spi::{Operation, SpiDevice};
+
+use crate::color::TriColor;
+use crate::controller::Controller;
+use crate::driver::Ssd1681;
+use crate::graphics::{find_position, outside_display, DisplayMirror, DisplayRotation};
+use crate::interface::{DisplayError, WriteOnlyDataCommand};
+use crate::pin::{BusyPin, ResetPin};
+use crate::ram::RamPlane;
+use crate::{HEIGHT, WIDTH};
+
+mod instruction {
+    pub const READ: u8 = 0x03;
+    pub const WRITE: u8 = 0x02;
+    pub const WRITE_STATUS: u8 = 0x01;
+}
+
+/// Status register value for sequential mode with the HOLD pin disabled
+const SEQUENTIAL_MODE: u8 = 0x41;
+
+/// Bytes written per transaction when filling
+const FILL_CHUNK_SIZE: usize = 128;
+
+/// Bytes in one plane of the 1.54" panel
+const PLANE_LEN: usize = WIDTH as usize * HEIGHT as usize / 8;
+
+/// SRAM address of the black and white plane
+const BW_ADDRESS: u16 = 0;
+
+/// SRAM address of the red plane
+const RED_ADDRESS: u16 = PLANE_LEN as u16;
+
+/// 23K256 or 23K640 style SPI SRAM with 16 bit addresses
+pub struct SpiSram<SPI> {
+    spi: SPI,
+}
+
+impl<SPI: SpiDevice> SpiSram<SPI> {
+    /// Put the SRAM into sequential mode, where reads and writes run over page boundaries
+    pub fn new(spi: SPI) -> Result<Self, SPI::Error> {
+        let mut sram = SpiSram { spi };
+        sram.spi
+            .write(&[instruction::WRITE_STATUS, SEQUENTIAL_MODE])?;
+        Ok(sram)
+    }
+
+    /// Read `buffer.len()` bytes starting at `address`
+    pub fn read(&mut self, address: u16, buffer: &mut [u8]) -> Result<(), SPI::Error> {
+        self.spi.transaction(&mut [
+            Operation::Write(&header(instruction::READ, address)),
+            Operation::Read(buffer),
+        ])
+    }
+
+    /// Write `data` starting at `address`
+    pub fn write(&mut self, address: u16, data: &[u8]) -> Result<(), SPI::Error> {
+        self.spi.transaction(&mut [
+            Operation::Write(&header(instruction::WRITE, address)),
+            Operation::Write(data),
+        ])
+    }
+
+    /// Write `value` to `len` bytes starting at `address`
+    pub fn fill(&mut self, address: u16, value: u8, len: usize) -> Result<(), SPI::Error> {
+        let chunk = [value; FILL_CHUNK_SIZE];
+        let mut offset = 0;
+        while offset < len {
+            let n = (len - offset).min(FILL_CHUNK_SIZE);
+            self.write(address + offset as u16, &chunk[..n])?;
+            offset += n;
+        }
+        Ok(())
+    }
+
+    /// Return the SPI device
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+}
+
+fn header(instruction: u8, address: u16) -> [u8; 3] {
+    let [high, low] = address.to_be_bytes();
+    [instruction, high, low]
+}
+
+/// Error returned by [`SramDisplay::upload`]
+#[derive(Debug)]
+pub enum SramError<E> {
+    /// SPI error talking to the SRAM
+    Sram(E),
+    /// Error talking to the controller
+    Display(DisplayError),
+}
+
+impl<E> From<DisplayError> for SramError<E> {
+    fn from(error: DisplayError) -> Self {
+        SramError::Display(error)
+    }
+}
+
+/// Tri-color frame buffer for the 1.54" panel kept in an [`SpiSram`]
+///
+/// Both planes take 10000 bytes, so this needs a 23K256 or bigger, the 8 KB of a 23K640 are
+/// not enough. Draw on it like on a [`Screen`](crate::screen::Screen), or on a single plane
+/// through [`bw`](Self::bw) and [`red`](Self::red), then [`upload`](Self::upload) it.
+///
+/// Every pixel costs an SRAM read and write, consecutive pixels in the same byte share them.
+pub struct SramDisplay<SPI> {
+    sram: SpiSram<SPI>,
+    rotation: DisplayRotation,
+    mirror: DisplayMirror,
+    bw_dirty: bool,
+    red_dirty: bool,
+}
+
+impl<SPI: SpiDevice> SramDisplay<SPI> {
+    /// Use `sram` for the frame buffer
+    ///
+    /// The SRAM content is not touched, call [`clear`](Self::clear) to start from a known
+    /// frame.
+    pub fn new(sram: SpiSram<SPI>) -> Self {
+        SramDisplay {
+            sram,
+            rotation: DisplayRotation::default(),
+            mirror: DisplayMirror::default(),
+            // not sent to the controller yet
+            bw_dirty: true,
+            red_dirty: true,
+        }
+    }
+
+    /// Fill the whole frame with `color`
+    pub fn clear(&mut self, color: TriColor) -> Result<(), SPI::Error> {
+        let (bw, red) = color.plane_bytes();
+        self.sram.fill(BW_ADDRESS, bw, PLANE_LEN)?;
+        self.sram.fill(RED_ADDRESS, red, PLANE_LEN)?;
+        self.bw_dirty = true;
+        self.red_dirty = true;
+        Ok(())
+    }
+
+    /// Sets the rotation used while drawing
+    pub fn set_rotation(&mut self, rotation: DisplayRotation) {
+        self.rotation = rotation;
+    }
+
+    /// Get the current rotation
+    pub fn rotation(&self) -> DisplayRotation {
+        self.rotation
+    }
+
+    /// Sets the mirroring used while drawing
+    pub fn set_mirror(&mut self, mirror: DisplayMirror) {
+        self.mirror = mirror;
+    }
+
+    /// Get the current mirroring
+    pub fn mirror(&self) -> DisplayMirror {
+        self.mirror
+    }
+
+    /// The black and white plane, `BinaryColor::On` is black
+    pub fn bw(&mut self) -> SramPlane<'_, SPI> {
+        SramPlane {
+            display: self,
+            plane: RamPlane::BlackWhite,
+        }
+    }
+
+    /// The red plane, `BinaryColor::On` is red
+    pub fn red(&mut self) -> SramPlane<'_, SPI> {
+        SramPlane {
+            display: self,
+            plane: RamPlane::Red,
+        }
+    }
+
+    /// Stream the planes changed since the last upload to the controller
+    ///
+    /// Show them with [`display_frame`](Ssd1681::display_frame) afterwards.
+    ///
+    /// # Panics
+    ///
+    /// If the panel of `ssd1681` is not 200x200.
+    pub fn upload<DI, BUSY, RST, C, const SHADOW_ROWS: usize>(
+        &mut self,
+        ssd1681: &mut Ssd1681<DI, BUSY, RST, C, SHADOW_ROWS>,
+    ) -> Result<(), SramError<SPI::Error>>
+    where
+        DI: WriteOnlyDataCommand,
+        BUSY: BusyPin,
+        RST: ResetPin,
+        C: Controller,
+    {
+        assert_eq!(ssd1681.size().buffer_len(), PLANE_LEN);
+        if self.bw_dirty {
+            self.upload_plane(ssd1681, RamPlane::BlackWhite)?;
+            self.bw_dirty = false;
+        }
+        if self.red_dirty {
+            self.upload_plane(ssd1681, RamPlane::Red)?;
+            self.red_dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Return the SRAM
+    pub fn release(self) -> SpiSram<SPI> {
+        self.sram
+    }
+
+    fn upload_plane<DI, BUSY, RST, C, const SHADOW_ROWS: usize>(
+        &mut self,
+        ssd1681: &mut Ssd1681<DI, BUSY, RST, C, SHADOW_ROWS>,
+        plane: RamPlane,
+    ) -> Result<(), SramError<SPI::Error>>
+    where
+        DI: WriteOnlyDataCommand,
+        BUSY: BusyPin,
+        RST: ResetPin,
+        C: Controller,
+    {
+        let address = plane_address(plane);
+        let sram = &mut self.sram;
+        ssd1681.stream_frame(plane, |offset, chunk| {
+            sram.read(address + offset as u16, chunk)
+                .map_err(SramError::Sram)
+        })
+    }
+
+    // Set the bit of `point` in `plane`, going through the byte held in `cache`
+    fn set_pixel(
+        &mut self,
+        cache: &mut Option<(u16, u8)>,
+        plane: RamPlane,
+        point: Point,
+        on: bool,
+    ) -> Result<(), SPI::Error> {
+        let (width, height) = (WIDTH.into(), HEIGHT.into());
+        if outside_display(point, width, height, self.rotation) {
+            return Ok(());
+        }
+        let (index, bit) = find_position(
+            point.x as u32,
+            point.y as u32,
+            width,
+            height,
+            self.rotation,
+            self.mirror,
+        );
+        let address = plane_address(plane) + index as u16;
+
+        let byte = match *cache {
+            Some((cached, byte)) if cached == address => byte,
+            _ => {
+                self.flush_cache(cache)?;
+                let mut byte = [0];
+                self.sram.read(address, &mut byte)?;
+                byte[0]
+            }
+        };
+        // the black and white plane is inverted, a set bit is white
+        let set = on != (plane == RamPlane::BlackWhite);
+        *cache = Some((address, if set { byte | bit } else { byte & !bit }));
+
+        match plane {
+            RamPlane::BlackWhite => self.bw_dirty = true,
+            RamPlane::Red => self.red_dirty = true,
+        }
+        Ok(())
+    }
+
+    fn flush_cache(&mut self, cache: &mut Option<(u16, u8)>) -> Result<(), SPI::Error> {
+        match cache.take() {
+            Some((address, byte)) => self.sram.write(address, &[byte]),
+            None => Ok(()),
+        }
+    }
+}
+
+fn plane_address(plane: RamPlane) -> u16 {
+    match plane {
+        RamPlane::BlackWhite => BW_ADDRESS,
+        RamPlane::Red => RED_ADDRESS,
+    }
+}
+
+impl<SPI> Dimensions for SramDisplay<SPI> {
+    fn bounding_box(&self) -> Rectangle {
+        Rectangle::new(Point::new(0, 0), Size::new(WIDTH as u32, HEIGHT as u32))
+    }
+}
+
+impl<SPI: SpiDevice> DrawTarget for SramDisplay<SPI> {
+    type Color = TriColor;
+    type Error = SPI::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (mut bw, mut red) = (None, None);
+        for Pixel(point, color) in pixels.into_iter() {
+            // `on` draws black and red
+            let (bw_bit, red_bit) = color.plane_bits();
+            if let Some(white) = bw_bit {
+                self.set_pixel(&mut bw, RamPlane::BlackWhite, point, !white)?;
+            }
+            self.set_pixel(&mut red, RamPlane::Red, point, red_bit)?;
+        }
+        self.flush_cache(&mut bw)?;
+        self.flush_cache(&mut red)
+    }
+}
+
+/// One plane of a [`SramDisplay`] to draw on with [`BinaryColor`]
+pub struct SramPlane<'a, SPI> {
+    display: &'a mut SramDisplay<SPI>,
+    plane: RamPlane,
+}
+
+impl<SPI> Dimensions for SramPlane<'_, SPI> {
+    fn bounding_box(&self) -> Rectangle {
+        self.display.bounding_box()
+    }
+}
+
+impl<SPI: SpiDevice> DrawTarget for SramPlane<'_, SPI> {
+    type Color = BinaryColor;
+    type Error = SPI::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let mut cache = None;
+        for Pixel(point, color) in pixels.into_iter() {
+            self.display
+                .set_pixel(&mut cache, self.plane, point, color.is_on())?;
+        }
+        self.display.flush_cache(&mut cache)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SpiSram, SramDisplay, PLANE_LEN, RED_ADDRESS};
+    use crate::cmd;
+    use crate::color::TriColor;
+    use crate::mock::{Sim, SramChip};
+    use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+
+    fn display(chip: &SramChip) -> SramDisplay<SramChip> {
+        SramDisplay::new(SpiSram::new(chip.clone()).unwrap())
+    }
+
+    #[test]
+    fn drawing_lands_in_sram() {
+        let chip = SramChip::new(32 * 1024);
+        let mut display = display(&chip);
+        assert_eq!(chip.mode(), 0x41);
+
+        display.clear(TriColor::White).unwrap();
+        Pixel(Point::new(0, 0), TriColor::Black)
+            .draw(&mut display)
+            .unwrap();
+        Pixel(Point::new(9, 1), TriColor::Red)
+            .draw(&mut display)
+            .unwrap();
+        Pixel(Point::new(1, 0), BinaryColor::On)
+            .draw(&mut display.red())
+            .unwrap();
+
+        let memory = chip.memory();
+        assert!(memory[1..PLANE_LEN].iter().all(|&b| b == 0xFF));
+        assert_eq!(memory[0], 0x7F);
+        let red = &memory[RED_ADDRESS as usize..][..PLANE_LEN];
+        assert_eq!(red[0], 0x40);
+        assert_eq!(red[26], 0x40);
+        assert_eq!(red.iter().filter(|&&b| b != 0).count(), 2);
+    }
+
+    #[test]
+    fn upload_streams_changed_planes() {
+        let chip = SramChip::new(32 * 1024);
+        let mut display = display(&chip);
+        display.clear(TriColor::Red).unwrap();
+        Pixel(Point::new(0, 0), TriColor::Black)
+            .draw(&mut display)
+            .unwrap();
+
+        let sim = Sim::new();
+        let mut ssd1681 = sim.driver();
+        display.upload(&mut ssd1681).unwrap();
+
+        let memory = chip.memory();
+        assert_eq!(
+            sim.data_for(cmd::WRITE_BW_DATA).unwrap(),
+            memory[..PLANE_LEN]
+        );
+        assert_eq!(
+            sim.data_for(cmd::WRITE_RED_DATA).unwrap(),
+            memory[PLANE_LEN..2 * PLANE_LEN]
+        );
+
+        sim.clear();
+        display.upload(&mut ssd1681).unwrap();
+        Pixel(Point::new(0, 0), BinaryColor::Off)
+            .draw(&mut display.bw())
+            .unwrap();
+        display.upload(&mut ssd1681).unwrap();
+        assert_eq!(sim.data_for(cmd::WRITE_BW_DATA).unwrap()[0], 0xFF);
+        assert_eq!(sim.data_for(cmd::WRITE_RED_DATA), None);
+    }
+}
@@ -0,0 +1,128 @@
+//! Controller status and panel diagnostics
+
+/// Chip ID the SSD1681 reports in the status bit register
+pub const CHIP_ID: u8 = 0b01;
+
+/// Contents of the status bit register (0x2F)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Status {
+    /// The last HV ready detection found the high voltages ready
+    pub hv_ready: bool,
+    /// The last VCI detection found VCI below the detection level
+    pub vci_low: bool,
+    /// The controller is busy
+    pub busy: bool,
+    /// Chip ID, [`CHIP_ID`] for a responding SSD1681
+    pub chip_id: u8,
+}
+
+impl Status {
+    /// Parses the status bit register
+    pub fn from_byte(value: u8) -> Self {
+        Status {
+            // the flags are active low
+            hv_ready: value & 0b10_0000 == 0,
+            vci_low: value & 0b01_0000 != 0,
+            busy: value & 0b100 != 0,
+            chip_id: value & 0b11,
+        }
+    }
+}
+
+impl From<u8> for Status {
+    fn from(value: u8) -> Self {
+        Status::from_byte(value)
+    }
+}
+
+/// Detection level for the VCI low voltage detection (0x15)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VciLevel {
+    /// 2.3V
+    V2_3,
+    /// 2.4V
+    V2_4,
+    /// 2.5V
+    #[default]
+    V2_5,
+    /// 2.6V
+    V2_6,
+    /// 2.7V
+    V2_7,
+}
+
+impl VciLevel {
+    pub(crate) fn flag(self) -> u8 {
+        match self {
+            VciLevel::V2_3 => 0b011,
+            VciLevel::V2_4 => 0b100,
+            VciLevel::V2_5 => 0b101,
+            VciLevel::V2_6 => 0b110,
+            VciLevel::V2_7 => 0b111,
+        }
+    }
+}
+
+/// Result of [`Ssd1681::self_test`](crate::driver::Ssd1681::self_test)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Status read before running the detections
+    pub status: Status,
+    /// The controller answered with the expected chip ID
+    pub responding: bool,
+    /// The booster reached the high voltages needed to drive the panel
+    pub hv_ready: bool,
+    /// VCI stayed above the detection level
+    pub vci_ok: bool,
+}
+
+impl Diagnostic {
+    /// Everything needed for a display update is in order
+    pub fn is_ok(&self) -> bool {
+        self.responding && self.hv_ready && self.vci_ok
+    }
+
+    /// The most likely reason the panel does not update, if any
+    pub fn fault(&self) -> Option<Fault> {
+        if !self.responding {
+            Some(Fault::NotResponding)
+        } else if !self.vci_ok {
+            Some(Fault::LowVoltage)
+        } else if !self.hv_ready {
+            Some(Fault::HighVoltageNotReady)
+        } else {
+            None
+        }
+    }
+}
+
+/// Reason a panel failed its self test
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Nothing answered on the bus, the panel is missing or not connected
+    NotResponding,
+    /// The supply voltage is below the detection level
+    LowVoltage,
+    /// The booster could not generate the driving voltages
+    HighVoltageNotReady,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_status() {
+        let status = Status::from(0b0000_0001);
+        assert!(status.hv_ready);
+        assert!(!status.vci_low);
+        assert!(!status.busy);
+        assert_eq!(status.chip_id, CHIP_ID);
+
+        let status = Status::from(0b0011_0100);
+        assert!(!status.hv_ready);
+        assert!(status.vci_low);
+        assert!(status.busy);
+        assert_eq!(status.chip_id, 0);
+    }
+}
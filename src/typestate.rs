@@ -0,0 +1,316 @@
+//! Power states of the controller checked at compile time
+//!
+//! [`Ssd1681`] wraps [`driver::Ssd1681`] and tracks in its type if the
+//! controller is initialised, asleep or refreshing. Frames can only be sent to a [`Ready`]
+//! controller, so forgetting `init` or sending to a sleeping panel fails to compile:
+//!
+//! ```text
+//! let ssd1681 = Ssd1681::new(interface, busy, rst); // Ssd1681<Uninit, ..>
+//! let mut ssd1681 = ssd1681.init(&mut delay)?;      // Ssd1681<Ready, ..>
+//! ssd1681.update_bw_frame(display.buffer())?;
+//! let ssd1681 = ssd1681.start_refresh()?;           // Ssd1681<Refreshing, ..>
+//! let ssd1681 = ssd1681.wait();                     // Ssd1681<Ready, ..>
+//! let ssd1681 = ssd1681.sleep()?;                   // Ssd1681<Sleeping, ..>
+//! let ssd1681 = ssd1681.wake_up(&mut delay)?;       // Ssd1681<Ready, ..>
+//! ```
+//!
+//! A transition that fails returns a [`TransitionError`] holding the driver in the state it
+//! started from, so the panel can still be put to sleep or released.
+//!
+//! The untyped driver stays available through [`Ssd1681::into_inner`].
+
+// every transition spells out the full driver type
+#![allow(clippy::type_complexity)]
+// handing the driver back is the point, there is no heap to box it on
+#![allow(clippy::result_large_err)]
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use embedded_hal::delay::DelayNs;
+
+use crate::driver::{self, DeepSleepMode};
+use crate::interface::{DisplayError, WriteOnlyDataCommand};
+use crate::pin::{BusyPin, ResetPin};
+
+/// The controller has not been initialised
+pub struct Uninit;
+
+/// The controller is initialised and idle
+pub struct Ready;
+
+/// The controller is in deep sleep
+pub struct Sleeping;
+
+/// The controller is refreshing the panel
+pub struct Refreshing;
+
+/// A failed transition
+///
+/// Also returned by the `release_sleeping` methods of the drivers and of
+/// `Screen` when the controller could not be put to sleep.
+pub struct TransitionError<T> {
+    /// The driver, still in the state the transition started from
+    ///
+    /// The controller may have received part of the transition.
+    pub driver: T,
+    /// What went wrong
+    pub error: DisplayError,
+}
+
+impl<T> fmt::Debug for TransitionError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransitionError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> From<TransitionError<T>> for DisplayError {
+    fn from(error: TransitionError<T>) -> Self {
+        error.error
+    }
+}
+
+/// Display driver with its power state in the type
+pub struct Ssd1681<STATE, DI, BUSY, RST> {
+    driver: driver::Ssd1681<DI, BUSY, RST>,
+    _state: PhantomData<STATE>,
+}
+
+impl<STATE, DI, BUSY, RST> Ssd1681<STATE, DI, BUSY, RST>
+where
+    DI: WriteOnlyDataCommand,
+    BUSY: BusyPin,
+    RST: ResetPin,
+{
+    fn into_state<NEXT>(self) -> Ssd1681<NEXT, DI, BUSY, RST> {
+        Ssd1681 {
+            driver: self.driver,
+            _state: PhantomData,
+        }
+    }
+
+    // Move into `NEXT` if `result` is ok, stay in the current state otherwise
+    fn transition<NEXT>(
+        self,
+        result: Result<(), DisplayError>,
+    ) -> Result<Ssd1681<NEXT, DI, BUSY, RST>, TransitionError<Self>> {
+        match result {
+            Ok(()) => Ok(self.into_state()),
+            Err(error) => Err(TransitionError {
+                driver: self,
+                error,
+            }),
+        }
+    }
+
+    /// Give up the state tracking and return the untyped driver
+    pub fn into_inner(self) -> driver::Ssd1681<DI, BUSY, RST> {
+        self.driver
+    }
+}
+
+impl<DI, BUSY, RST> Ssd1681<Uninit, DI, BUSY, RST>
+where
+    DI: WriteOnlyDataCommand,
+    BUSY: BusyPin,
+    RST: ResetPin,
+{
+    /// Create the display driver without talking to the controller
+    pub fn new(interface: DI, busy: BUSY, rst: RST) -> Self {
+        Ssd1681 {
+            driver: driver::Ssd1681::new_uninit(interface, busy, rst, driver::PANEL_1IN54),
+            _state: PhantomData,
+        }
+    }
+
+    /// Initialise the controller
+    pub fn init<DELAY: DelayNs>(
+        mut self,
+        delay: &mut DELAY,
+    ) -> Result<Ssd1681<Ready, DI, BUSY, RST>, TransitionError<Self>> {
+        let result = self.driver.init(delay);
+        self.transition(result)
+    }
+}
+
+impl<DI, BUSY, RST> Ssd1681<Ready, DI, BUSY, RST>
+where
+    DI: WriteOnlyDataCommand,
+    BUSY: BusyPin,
+    RST: ResetPin,
+{
+    /// The untyped driver, for everything the typed API does not cover
+    ///
+    /// An escape hatch like [`into_inner`](Ssd1681::into_inner): the type can not follow what
+    /// is done through it. Sending the controller to sleep or leaving a refresh running makes
+    /// the state wrong, only use it for what keeps the controller ready and idle.
+    pub fn driver(&mut self) -> &mut driver::Ssd1681<DI, BUSY, RST> {
+        &mut self.driver
+    }
+
+    /// Update the whole BW buffer on the display driver
+    pub fn update_bw_frame(&mut self, buffer: &[u8]) -> Result<(), DisplayError> {
+        self.driver.update_bw_frame(buffer)
+    }
+
+    /// Update the whole Red buffer on the display driver
+    pub fn update_red_frame(&mut self, buffer: &[u8]) -> Result<(), DisplayError> {
+        self.driver.update_red_frame(buffer)
+    }
+
+    /// Make the whole black and white frame on the display driver white
+    pub fn clear_bw_frame(&mut self) -> Result<(), DisplayError> {
+        self.driver.clear_bw_frame()
+    }
+
+    /// Make the whole red frame on the display driver white
+    pub fn clear_red_frame(&mut self) -> Result<(), DisplayError> {
+        self.driver.clear_red_frame()
+    }
+
+    /// Update the whole display and wait until it is done
+    pub fn display_frame(&mut self) -> Result<(), DisplayError> {
+        self.driver.display_frame()
+    }
+
+    /// Start an update of the whole display
+    pub fn start_refresh(
+        mut self,
+    ) -> Result<Ssd1681<Refreshing, DI, BUSY, RST>, TransitionError<Self>> {
+        let result = self.driver.start_refresh();
+        self.transition(result)
+    }
+
+    /// Put the controller into deep sleep, keeping the RAM
+    pub fn sleep(self) -> Result<Ssd1681<Sleeping, DI, BUSY, RST>, TransitionError<Self>> {
+        self.sleep_with_mode(DeepSleepMode::RetainRam)
+    }
+
+    /// Put the controller into deep sleep with the given mode
+    pub fn sleep_with_mode(
+        mut self,
+        mode: DeepSleepMode,
+    ) -> Result<Ssd1681<Sleeping, DI, BUSY, RST>, TransitionError<Self>> {
+        let result = self.driver.sleep(mode);
+        self.transition(result)
+    }
+
+    /// Put the controller into deep sleep with the given mode, then return the transport and
+    /// the pins
+    pub fn release_sleeping(
+        self,
+        mode: DeepSleepMode,
+    ) -> Result<(DI, BUSY, RST), TransitionError<Self>> {
+        self.sleep_with_mode(mode).map(Ssd1681::release)
+    }
+}
+
+impl<DI, BUSY, RST> Ssd1681<Refreshing, DI, BUSY, RST>
+where
+    DI: WriteOnlyDataCommand,
+    BUSY: BusyPin,
+    RST: ResetPin,
+{
+    /// Check if the refresh is still running
+    pub fn is_busy(&mut self) -> bool {
+        self.driver.is_busy()
+    }
+
+    /// The refresh is done, if it is not `Err(self)` is returned to try again later
+    ///
+    /// The typed counterpart of [`poll_refresh`](driver::Ssd1681::poll_refresh), and like it
+    /// blocks until the refresh is done without a BUSY pin.
+    pub fn try_finish(mut self) -> Result<Ssd1681<Ready, DI, BUSY, RST>, Self> {
+        if self.driver.is_busy() {
+            Err(self)
+        } else {
+            Ok(self.into_state())
+        }
+    }
+
+    /// Wait until the refresh is done
+    pub fn wait(mut self) -> Ssd1681<Ready, DI, BUSY, RST> {
+        self.driver.wait_until_idle();
+        self.into_state()
+    }
+}
+
+impl<DI, BUSY, RST> Ssd1681<Sleeping, DI, BUSY, RST>
+where
+    DI: WriteOnlyDataCommand,
+    BUSY: BusyPin,
+    RST: ResetPin,
+{
+    /// Wake the controller up and initialise it again
+    ///
+    /// Fails without a RST pin, see [`driver::Ssd1681::wake_up`].
+    pub fn wake_up<DELAY: DelayNs>(
+        mut self,
+        delay: &mut DELAY,
+    ) -> Result<Ssd1681<Ready, DI, BUSY, RST>, TransitionError<Self>> {
+        let result = self.driver.wake_up(delay);
+        self.transition(result)
+    }
+
+    /// Leave the panel asleep and return the transport and the pins
+    pub fn release(self) -> (DI, BUSY, RST) {
+        self.driver.release()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ssd1681;
+    use crate::cmd;
+    use crate::interface::{DataFormat, DisplayError, SpiInterface, WriteOnlyDataCommand};
+    use crate::mock::{Idle, NoDelay, Pin, Sim};
+
+    // A transport whose bus is gone
+    struct Broken;
+
+    impl WriteOnlyDataCommand for Broken {
+        fn send_commands(&mut self, _cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+            Err(DisplayError::BusWriteError)
+        }
+
+        fn send_data(&mut self, _buf: DataFormat<'_>) -> Result<(), DisplayError> {
+            Err(DisplayError::BusWriteError)
+        }
+    }
+
+    #[test]
+    fn power_cycle() {
+        let sim = Sim::new();
+
+        let ssd1681 = Ssd1681::new(SpiInterface::new(sim.spi(), sim.dc()), Idle, Pin);
+        assert_eq!(sim.data_for(cmd::SW_RESET), None);
+
+        let mut ssd1681 = ssd1681.init(&mut NoDelay).unwrap();
+        assert!(sim.data_for(cmd::SW_RESET).is_some());
+
+        ssd1681.update_bw_frame(&[0x00; 8]).unwrap();
+        let ssd1681 = ssd1681.start_refresh().unwrap();
+        let ssd1681 = ssd1681.try_finish().ok().unwrap();
+
+        let ssd1681 = ssd1681.sleep().unwrap();
+        assert_eq!(sim.data_for(cmd::DEEP_SLEEP_MODE).unwrap(), [0b01]);
+
+        sim.clear();
+        let _ssd1681 = ssd1681.wake_up(&mut NoDelay).unwrap();
+        assert!(sim.data_for(cmd::SW_RESET).is_some());
+    }
+
+    #[test]
+    fn failed_transition_returns_driver() {
+        let error = Ssd1681::new(Broken, Idle, Pin)
+            .init(&mut NoDelay)
+            .err()
+            .unwrap();
+        assert!(matches!(error.error, DisplayError::BusWriteError));
+
+        // still uninitialised, and the pins can be had back
+        let (_broken, _busy, _rst) = error.driver.into_inner().release();
+    }
+}
@@ -0,0 +1,289 @@
+//! Analog settings for custom waveforms
+//!
+//! All voltages are given in millivolts and checked against the range the controller supports,
+//! values between two register steps are rounded down to the lower magnitude.
+
+/// VCOM register values for -0.2V to -3.0V in 100mV steps
+const VCOM_TABLE: [u8; 29] = [
+    0x08, 0x0B, 0x10, 0x14, 0x17, 0x1B, 0x20, 0x24, 0x28, 0x2C, 0x2F, 0x34, 0x37, 0x3C, 0x40, 0x44,
+    0x48, 0x4B, 0x50, 0x54, 0x58, 0x5B, 0x5F, 0x64, 0x68, 0x6C, 0x70, 0x73, 0x78,
+];
+
+/// Gate driving voltage VGH (0x03), 12V to 20V in 500mV steps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GateVoltage(u8);
+
+impl GateVoltage {
+    /// Gate voltage of `millivolts`, `None` if outside 12000..=20000
+    pub fn from_millivolts(millivolts: u16) -> Option<Self> {
+        if !(12_000..=20_000).contains(&millivolts) {
+            return None;
+        }
+        Some(GateVoltage(0x07 + ((millivolts - 12_000) / 500) as u8))
+    }
+
+    /// The voltage in millivolts
+    pub fn millivolts(self) -> u16 {
+        12_000 + u16::from(self.0 - 0x07) * 500
+    }
+
+    pub(crate) fn register(self) -> u8 {
+        self.0
+    }
+}
+
+impl Default for GateVoltage {
+    /// 20V, the value after reset
+    fn default() -> Self {
+        GateVoltage(0x17)
+    }
+}
+
+/// Positive source driving voltage VSH1/VSH2 (0x04)
+///
+/// 2.4V to 8.8V in 100mV steps and 9V to 17V in 200mV steps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceHighVoltage(u8);
+
+impl SourceHighVoltage {
+    /// Source voltage of `millivolts`, `None` if outside 2400..=17000
+    pub fn from_millivolts(millivolts: u16) -> Option<Self> {
+        match millivolts {
+            2_400..=8_899 => Some(SourceHighVoltage(0x8E + ((millivolts - 2_400) / 100) as u8)),
+            8_900..=8_999 => Some(SourceHighVoltage(0xCE)),
+            9_000..=17_000 => Some(SourceHighVoltage(0x23 + ((millivolts - 9_000) / 200) as u8)),
+            _ => None,
+        }
+    }
+
+    /// The voltage in millivolts
+    pub fn millivolts(self) -> u16 {
+        if self.0 & 0x80 != 0 {
+            2_400 + u16::from(self.0 - 0x8E) * 100
+        } else {
+            9_000 + u16::from(self.0 - 0x23) * 200
+        }
+    }
+
+    pub(crate) fn register(self) -> u8 {
+        self.0
+    }
+}
+
+/// Negative source driving voltage VSL (0x04), -9V to -17V in 500mV steps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceLowVoltage(u8);
+
+impl SourceLowVoltage {
+    /// Source voltage of `millivolts`, `None` if outside -17000..=-9000
+    pub fn from_millivolts(millivolts: i16) -> Option<Self> {
+        if !(-17_000..=-9_000).contains(&millivolts) {
+            return None;
+        }
+        let steps = ((-9_000 - millivolts) / 500) as u8;
+        Some(SourceLowVoltage(0x1A + steps * 2))
+    }
+
+    /// The voltage in millivolts
+    pub fn millivolts(self) -> i16 {
+        -9_000 - i16::from((self.0 - 0x1A) / 2) * 500
+    }
+
+    pub(crate) fn register(self) -> u8 {
+        self.0
+    }
+}
+
+/// VCOM voltage (0x2C), -0.2V to -3.0V in 100mV steps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VcomVoltage(u8);
+
+impl VcomVoltage {
+    /// VCOM of `millivolts`, `None` if outside -3000..=-200
+    pub fn from_millivolts(millivolts: i16) -> Option<Self> {
+        if !(-3_000..=-200).contains(&millivolts) {
+            return None;
+        }
+        let index = ((-200 - millivolts) / 100) as usize;
+        Some(VcomVoltage(VCOM_TABLE[index]))
+    }
+
+    /// The voltage in millivolts
+    pub fn millivolts(self) -> i16 {
+        let index = VCOM_TABLE.iter().position(|&v| v == self.0).unwrap_or(0);
+        -200 - index as i16 * 100
+    }
+
+    pub(crate) fn register(self) -> u8 {
+        self.0
+    }
+}
+
+/// One phase of the booster soft start
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SoftStartPhase {
+    strength: u8,
+    min_off_time: u8,
+    duration_ms: u8,
+}
+
+impl SoftStartPhase {
+    /// Create a phase
+    ///
+    /// * `strength` is the driving strength from 1 (weakest) to 8
+    /// * `min_off_time` is the raw minimum GDR off time setting from 4 to 15
+    /// * `duration_ms` is 10, 20, 30 or 40
+    pub fn new(strength: u8, min_off_time: u8, duration_ms: u8) -> Option<Self> {
+        if !(1..=8).contains(&strength)
+            || !(4..=15).contains(&min_off_time)
+            || !matches!(duration_ms, 10 | 20 | 30 | 40)
+        {
+            return None;
+        }
+        Some(SoftStartPhase {
+            strength,
+            min_off_time,
+            duration_ms,
+        })
+    }
+
+    fn register(self) -> u8 {
+        0x80 | (self.strength - 1) << 4 | self.min_off_time
+    }
+
+    fn duration(self) -> u8 {
+        self.duration_ms / 10 - 1
+    }
+}
+
+/// Booster soft start (0x0C)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoosterSoftStart {
+    /// Phase 1
+    pub phase1: SoftStartPhase,
+    /// Phase 2
+    pub phase2: SoftStartPhase,
+    /// Phase 3
+    pub phase3: SoftStartPhase,
+}
+
+impl BoosterSoftStart {
+    pub(crate) fn register(self) -> [u8; 4] {
+        [
+            self.phase1.register(),
+            self.phase2.register(),
+            self.phase3.register(),
+            self.phase3.duration() << 4 | self.phase2.duration() << 2 | self.phase1.duration(),
+        ]
+    }
+}
+
+impl Default for BoosterSoftStart {
+    /// The values after reset
+    fn default() -> Self {
+        BoosterSoftStart {
+            phase1: SoftStartPhase {
+                strength: 1,
+                min_off_time: 0xB,
+                duration_ms: 40,
+            },
+            phase2: SoftStartPhase {
+                strength: 2,
+                min_off_time: 0xC,
+                duration_ms: 40,
+            },
+            phase3: SoftStartPhase {
+                strength: 2,
+                min_off_time: 0x6,
+                duration_ms: 10,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gate_voltage() {
+        assert_eq!(
+            GateVoltage::from_millivolts(12_000).unwrap().register(),
+            0x07
+        );
+        assert_eq!(
+            GateVoltage::from_millivolts(20_000).unwrap().register(),
+            0x17
+        );
+        assert_eq!(
+            GateVoltage::from_millivolts(15_250).unwrap().millivolts(),
+            15_000
+        );
+        assert_eq!(GateVoltage::from_millivolts(11_999), None);
+        assert_eq!(GateVoltage::from_millivolts(20_001), None);
+    }
+
+    #[test]
+    fn source_voltages() {
+        assert_eq!(
+            SourceHighVoltage::from_millivolts(2_400)
+                .unwrap()
+                .register(),
+            0x8E
+        );
+        assert_eq!(
+            SourceHighVoltage::from_millivolts(8_800)
+                .unwrap()
+                .register(),
+            0xCE
+        );
+        assert_eq!(
+            SourceHighVoltage::from_millivolts(15_000)
+                .unwrap()
+                .register(),
+            0x41
+        );
+        assert_eq!(
+            SourceHighVoltage::from_millivolts(17_000)
+                .unwrap()
+                .millivolts(),
+            17_000
+        );
+        assert_eq!(SourceHighVoltage::from_millivolts(17_200), None);
+
+        assert_eq!(
+            SourceLowVoltage::from_millivolts(-15_000)
+                .unwrap()
+                .register(),
+            0x32
+        );
+        assert_eq!(
+            SourceLowVoltage::from_millivolts(-17_000)
+                .unwrap()
+                .millivolts(),
+            -17_000
+        );
+        assert_eq!(SourceLowVoltage::from_millivolts(-8_000), None);
+    }
+
+    #[test]
+    fn vcom_and_soft_start() {
+        assert_eq!(VcomVoltage::from_millivolts(-200).unwrap().register(), 0x08);
+        assert_eq!(
+            VcomVoltage::from_millivolts(-3_000).unwrap().register(),
+            0x78
+        );
+        assert_eq!(
+            VcomVoltage::from_millivolts(-1_450).unwrap().millivolts(),
+            -1_400
+        );
+        assert_eq!(VcomVoltage::from_millivolts(0), None);
+
+        assert_eq!(
+            BoosterSoftStart::default().register(),
+            [0x8B, 0x9C, 0x96, 0x0F]
+        );
+        assert_eq!(SoftStartPhase::new(9, 4, 10), None);
+        assert_eq!(SoftStartPhase::new(1, 4, 15), None);
+    }
+}